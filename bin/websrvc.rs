@@ -1,6 +1,16 @@
-use std::sync::{Arc, Mutex};
+// the route filters below nest deeper than the default limit allows
+#![recursion_limit = "256"]
+
+use std::convert::Infallible;
+
+use futures_util::{SinkExt, Stream, StreamExt};
+use serde::Deserialize;
+use warp::http::StatusCode;
+use warp::ws::{Message, WebSocket, Ws};
 use warp::Filter;
 use x11wmgr::messages::*;
+use x11wmgr::rules::{default_rules_path, RuleSet};
+use x11wmgr::scenes::{default_scenes_path, SceneStore};
 use x11wmgr::*;
 
 #[tokio::main]
@@ -12,7 +22,17 @@ async fn main() {
 }
 
 async fn run() -> Result<(), Error> {
-    let wm = Arc::new(Mutex::new(WindowManager::new()?));
+    let mut wm = WindowManager::new()?;
+    if let Some(path) = default_rules_path() {
+        wm.set_rules(RuleSet::load_if_exists(path)?);
+    }
+    if let Some(path) = default_scenes_path() {
+        wm.set_scenes(SceneStore::open(path)?);
+    }
+
+    // the window manager handles X11 events on its own thread, in between the HTTP handlers'
+    // calls
+    let wm = WmHandle::spawn(wm);
 
     let api = warp::path("api").and(warp::path("windows"));
 
@@ -41,6 +61,55 @@ async fn run() -> Result<(), Error> {
         .and(warp::body::json())
         .and_then(handle_focus_window);
 
+    let raise_window = api
+        .and(warp::path("raise"))
+        .and(warp::post())
+        .and(with_wm(wm.clone()))
+        .and(warp::body::json())
+        .and_then(handle_raise_window);
+
+    let lower_window = api
+        .and(warp::path("lower"))
+        .and(warp::post())
+        .and(with_wm(wm.clone()))
+        .and(warp::body::json())
+        .and_then(handle_lower_window);
+
+    let stack_above = api
+        .and(warp::path("stack-above"))
+        .and(warp::post())
+        .and(with_wm(wm.clone()))
+        .and(warp::body::json())
+        .and_then(handle_stack_above);
+
+    let stack_below = api
+        .and(warp::path("stack-below"))
+        .and(warp::post())
+        .and(with_wm(wm.clone()))
+        .and(warp::body::json())
+        .and_then(handle_stack_below);
+
+    let set_layer = api
+        .and(warp::path("layer"))
+        .and(warp::post())
+        .and(with_wm(wm.clone()))
+        .and(warp::body::json())
+        .and_then(handle_set_layer);
+
+    let set_configure_policy = api
+        .and(warp::path("configure-policy"))
+        .and(warp::post())
+        .and(with_wm(wm.clone()))
+        .and(warp::body::json())
+        .and_then(handle_set_configure_policy);
+
+    let tile_windows = api
+        .and(warp::path("tile"))
+        .and(warp::post())
+        .and(with_wm(wm.clone()))
+        .and(warp::body::json())
+        .and_then(handle_tile_windows);
+
     let change_visibility = api
         .and(warp::path("visibility"))
         .and(warp::post())
@@ -69,103 +138,534 @@ async fn run() -> Result<(), Error> {
         .and(warp::body::json())
         .and_then(handle_change_zindex);
 
+    let close_windows = api
+        .and(warp::path("close"))
+        .and(warp::post())
+        .and(with_wm(wm.clone()))
+        .and(warp::body::json())
+        .and_then(handle_close_windows);
+
+    let kill_clients = api
+        .and(warp::path("kill"))
+        .and(warp::post())
+        .and(with_wm(wm.clone()))
+        .and(warp::body::json())
+        .and_then(handle_kill_clients);
+
+    let apply_layout = api
+        .and(warp::path("apply"))
+        .and(warp::post())
+        .and(with_wm(wm.clone()))
+        .and(warp::body::json())
+        .and_then(handle_apply_layout);
+
     let commit = api
         .and(warp::path("commit"))
         .and(warp::post())
         .and(with_wm(wm.clone()))
+        .and(warp::query::<CommitOptions>())
         .and_then(handle_commit);
 
+    let pending_changes = api
+        .and(warp::path("pending"))
+        .and(warp::get())
+        .and(with_wm(wm.clone()))
+        .and_then(handle_get_pending_changes);
+
+    let discard_changes = api
+        .and(warp::path("pending"))
+        .and(warp::delete())
+        .and(with_wm(wm.clone()))
+        .and_then(handle_discard_pending_changes);
+
+    let list_outputs = warp::path("api")
+        .and(warp::path("outputs"))
+        .and(warp::get())
+        .and(with_wm(wm.clone()))
+        .and_then(handle_list_outputs);
+
+    let set_layout = warp::path("api")
+        .and(warp::path("layout"))
+        .and(warp::post())
+        .and(with_wm(wm.clone()))
+        .and(warp::body::json())
+        .and_then(handle_set_layout);
+
+    let move_to_workspace = api
+        .and(warp::path("workspace"))
+        .and(warp::post())
+        .and(with_wm(wm.clone()))
+        .and(warp::body::json())
+        .and_then(handle_move_to_workspace);
+
+    let api_workspaces = warp::path("api").and(warp::path("workspaces"));
+
+    let list_workspaces = api_workspaces
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_wm(wm.clone()))
+        .and_then(handle_list_workspaces);
+
+    let switch_workspace = api_workspaces
+        .and(warp::path("switch"))
+        .and(warp::post())
+        .and(with_wm(wm.clone()))
+        .and(warp::body::json())
+        .and_then(handle_switch_workspace);
+
+    let api_rules = warp::path("api").and(warp::path("rules"));
+
+    let list_rules = api_rules
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_wm(wm.clone()))
+        .and_then(handle_list_rules);
+
+    let add_rule = api_rules
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_wm(wm.clone()))
+        .and(warp::body::json())
+        .and_then(handle_add_rule);
+
+    let remove_rule = api_rules
+        .and(warp::path::param())
+        .and(warp::delete())
+        .and(with_wm(wm.clone()))
+        .and_then(handle_remove_rule);
+
+    let api_scenes = warp::path("api").and(warp::path("scenes"));
+
+    let list_scenes = api_scenes
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_wm(wm.clone()))
+        .and_then(handle_list_scenes);
+
+    let save_scene = api_scenes
+        .and(warp::path::param())
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(with_wm(wm.clone()))
+        .and(warp::body::json())
+        .and_then(handle_save_scene);
+
+    let apply_scene = api_scenes
+        .and(warp::path::param())
+        .and(warp::path("apply"))
+        .and(warp::post())
+        .and(with_wm(wm.clone()))
+        .and_then(handle_apply_scene);
+
+    let delete_scene = api_scenes
+        .and(warp::path::param())
+        .and(warp::path::end())
+        .and(warp::delete())
+        .and(with_wm(wm.clone()))
+        .and_then(handle_delete_scene);
+
+    let api_history = warp::path("api").and(warp::path("history"));
+
+    let list_history = api_history
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_wm(wm.clone()))
+        .and_then(handle_list_history);
+
+    let undo = api_history
+        .and(warp::path("undo"))
+        .and(warp::post())
+        .and(with_wm(wm.clone()))
+        .and_then(handle_undo);
+
+    let redo = api_history
+        .and(warp::path("redo"))
+        .and(warp::post())
+        .and(with_wm(wm.clone()))
+        .and_then(handle_redo);
+
+    let api_events = warp::path("api").and(warp::path("events"));
+
+    let event_source = api_events
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_wm(wm.clone()))
+        .and_then(handle_event_source);
+
+    let event_socket = api_events
+        .and(warp::path("ws"))
+        .and(warp::ws())
+        .and(with_wm(wm.clone()))
+        .and_then(handle_event_socket);
+
     let routes = list_new_windows
         .or(list_visible_windows)
         .or(list_hidden_windows)
         .or(focus_window)
+        .or(raise_window)
+        .or(lower_window)
+        .or(stack_above)
+        .or(stack_below)
+        .or(set_layer)
+        .or(set_configure_policy)
+        .or(tile_windows)
         .or(change_visibility)
         .or(move_windows)
         .or(resize_windows)
         .or(change_zindex)
-        .or(commit);
+        .or(close_windows)
+        .or(kill_clients)
+        .or(apply_layout)
+        .or(commit)
+        .or(pending_changes)
+        .or(discard_changes)
+        .or(list_outputs)
+        .or(set_layout)
+        .or(move_to_workspace)
+        .or(list_workspaces)
+        .or(switch_workspace)
+        .or(list_rules)
+        .or(add_rule)
+        .or(remove_rule)
+        .or(list_scenes)
+        .or(save_scene)
+        .or(apply_scene)
+        .or(delete_scene)
+        .or(list_history)
+        .or(undo)
+        .or(redo)
+        .or(event_source)
+        .or(event_socket)
+        .recover(handle_rejection);
+
+    let server = warp::serve(routes).run(([127, 0, 0, 1], 3030));
 
-    warp::serve(routes).run(([127, 0, 0, 1], 3030)).await;
-    Ok(())
+    tokio::select! {
+        _ = server => Ok(()),
+        err = wm.stopped() => Err(err),
+    }
 }
 
-async fn handle_list_new_windows(
-    wm: Arc<Mutex<WindowManager>>,
-) -> Result<impl warp::Reply, warp::Rejection> {
-    let mut wm = wm.lock().unwrap();
-    let new_wins = wm.check_new();
+async fn handle_event_source(wm: WmHandle) -> Result<impl warp::Reply, warp::Rejection> {
+    let stream = wm.events().await?.map(|event| {
+        let json = serde_json::to_string(&event).unwrap();
+        Ok::<_, Infallible>(warp::sse::Event::default().data(json))
+    });
+    Ok(warp::sse::reply(warp::sse::keep_alive().stream(stream)))
+}
+
+async fn handle_event_socket(ws: Ws, wm: WmHandle) -> Result<impl warp::Reply, warp::Rejection> {
+    let events = wm.events().await?;
+    Ok(ws.on_upgrade(move |socket| send_events(socket, events)))
+}
+
+async fn send_events(socket: WebSocket, events: impl Stream<Item = Event>) {
+    let (mut tx_socket, _) = socket.split();
+    let mut events = Box::pin(events);
+
+    while let Some(event) = events.next().await {
+        let json = serde_json::to_string(&event).unwrap();
+        if tx_socket.send(Message::text(json)).await.is_err() {
+            break;
+        }
+    }
+}
+
+async fn handle_list_new_windows(wm: WmHandle) -> Result<impl warp::Reply, warp::Rejection> {
+    let new_wins = wm.check_new().await?;
     Ok(warp::reply::json(&Response::NewWindows(new_wins)))
 }
 
-async fn handle_list_visible_windows(
-    wm: Arc<Mutex<WindowManager>>,
-) -> Result<impl warp::Reply, warp::Rejection> {
-    let wm = wm.lock().unwrap();
-    let wins = wm.get_visible_wins();
+async fn handle_list_visible_windows(wm: WmHandle) -> Result<impl warp::Reply, warp::Rejection> {
+    let wins = wm.get_visible_wins().await?;
     Ok(warp::reply::json(&Response::VisibleWindows(wins)))
 }
 
-async fn handle_list_hidden_windows(
-    wm: Arc<Mutex<WindowManager>>,
-) -> Result<impl warp::Reply, warp::Rejection> {
-    let wm = wm.lock().unwrap();
-    let wins = wm.get_hidden_wins();
+async fn handle_list_hidden_windows(wm: WmHandle) -> Result<impl warp::Reply, warp::Rejection> {
+    let wins = wm.get_hidden_wins().await?;
     Ok(warp::reply::json(&Response::HiddenWindows(wins)))
 }
 
+async fn handle_raise_window(
+    wm: WmHandle,
+    id: Window,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let restacked = wm.raise_window(id).await?;
+    Ok(warp::reply::json(&Response::Restacked(restacked)))
+}
+
+async fn handle_lower_window(
+    wm: WmHandle,
+    id: Window,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let restacked = wm.lower_window(id).await?;
+    Ok(warp::reply::json(&Response::Restacked(restacked)))
+}
+
+#[derive(Deserialize)]
+struct SiblingOptions {
+    id: Window,
+    sibling: Window,
+}
+
+async fn handle_stack_above(
+    wm: WmHandle,
+    options: SiblingOptions,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let restacked = wm.stack_above(options.id, options.sibling).await?;
+    Ok(warp::reply::json(&Response::Restacked(restacked)))
+}
+
+async fn handle_stack_below(
+    wm: WmHandle,
+    options: SiblingOptions,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let restacked = wm.stack_below(options.id, options.sibling).await?;
+    Ok(warp::reply::json(&Response::Restacked(restacked)))
+}
+
+async fn handle_set_layer(
+    wm: WmHandle,
+    windows: Vec<WinLayer>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let changed = wm.set_layer(windows).await?;
+    Ok(warp::reply::json(&Response::LayerSet(changed)))
+}
+
+#[derive(Deserialize)]
+struct ConfigurePolicyOptions {
+    #[serde(default)]
+    id: Option<Window>,
+    policy: Option<ConfigurePolicy>,
+}
+
+async fn handle_set_configure_policy(
+    wm: WmHandle,
+    options: ConfigurePolicyOptions,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let is_set = wm.set_configure_policy(options.id, options.policy).await?;
+    Ok(warp::reply::json(&Response::ConfigurePolicySet(is_set)))
+}
+
+async fn handle_tile_windows(
+    wm: WmHandle,
+    windows: Vec<Window>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let tiled = wm.tile_windows(windows).await?;
+    Ok(warp::reply::json(&Response::WindowsTiled(tiled)))
+}
+
 async fn handle_focus_window(
-    wm: Arc<Mutex<WindowManager>>,
+    wm: WmHandle,
     id: Window,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    let mut wm = wm.lock().unwrap();
-    let is_focused = wm.focus_window(id)?;
+    let is_focused = wm.focus_window(id).await?;
     Ok(warp::reply::json(&Response::WindowFocused(is_focused)))
 }
 
 async fn handle_change_visibility(
-    wm: Arc<Mutex<WindowManager>>,
+    wm: WmHandle,
     win_vis: Vec<WinVisbilty>,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    let mut wm = wm.lock().unwrap();
-    let result = wm.change_visiblity(win_vis.into_iter());
+    let result = wm.change_visiblity(win_vis).await?;
     Ok(warp::reply::json(&Response::VisibiltyChanged(result)))
 }
 
 async fn handle_move_windows(
-    wm: Arc<Mutex<WindowManager>>,
+    wm: WmHandle,
     windows: Vec<WinMove>,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    let mut wm = wm.lock().unwrap();
-    wm.move_windows(windows.into_iter())?;
+    wm.move_windows(windows).await?;
     Ok(warp::reply::json(&Response::MoveComplete))
 }
 
 async fn handle_resize_windows(
-    wm: Arc<Mutex<WindowManager>>,
+    wm: WmHandle,
     windows: Vec<WinResize>,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    let mut wm = wm.lock().unwrap();
-    wm.resize_windows(windows.into_iter())?;
+    wm.resize_windows(windows).await?;
     Ok(warp::reply::json(&Response::ResizeComplete))
 }
 
 async fn handle_change_zindex(
-    wm: Arc<Mutex<WindowManager>>,
+    wm: WmHandle,
     win_indices: Vec<WinZIndex>,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    let mut wm = wm.lock().unwrap();
-    let result = wm.change_indices(win_indices.into_iter());
+    let result = wm.change_indices(win_indices).await?;
     Ok(warp::reply::json(&Response::ZIndexChanged(result)))
 }
 
-async fn handle_commit(wm: Arc<Mutex<WindowManager>>) -> Result<impl warp::Reply, warp::Rejection> {
-    let mut wm = wm.lock().unwrap();
-    wm.commit()?;
-    Ok(warp::reply::json(&Response::CommitComplete))
+async fn handle_close_windows(
+    wm: WmHandle,
+    windows: Vec<Window>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let closed = wm.close_windows(windows).await?;
+    Ok(warp::reply::json(&Response::WindowsClosed(closed)))
+}
+
+async fn handle_kill_clients(
+    wm: WmHandle,
+    windows: Vec<Window>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let killed = wm.kill_clients(windows).await?;
+    Ok(warp::reply::json(&Response::WindowsClosed(killed)))
+}
+
+async fn handle_list_outputs(wm: WmHandle) -> Result<impl warp::Reply, warp::Rejection> {
+    let outputs = wm.list_outputs().await?;
+    Ok(warp::reply::json(&Response::Outputs(outputs)))
+}
+
+async fn handle_set_layout(
+    wm: WmHandle,
+    spec: Option<LayoutSpec>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    wm.set_layout(spec).await?;
+    Ok(warp::reply::json(&Response::LayoutSet))
+}
+
+async fn handle_move_to_workspace(
+    wm: WmHandle,
+    windows: Vec<WinWorkspace>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let moved = wm.move_to_workspace(windows).await?;
+    Ok(warp::reply::json(&Response::MovedToWorkspace(moved)))
+}
+
+async fn handle_list_workspaces(wm: WmHandle) -> Result<impl warp::Reply, warp::Rejection> {
+    let workspaces = wm.list_workspaces().await?;
+    Ok(warp::reply::json(&Response::Workspaces(workspaces)))
+}
+
+async fn handle_switch_workspace(
+    wm: WmHandle,
+    name: String,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    wm.switch_workspace(name).await?;
+    Ok(warp::reply::json(&Response::WorkspaceSwitched))
+}
+
+async fn handle_list_rules(wm: WmHandle) -> Result<impl warp::Reply, warp::Rejection> {
+    let rules = wm.list_rules().await?;
+    Ok(warp::reply::json(&Response::Rules(rules)))
+}
+
+async fn handle_add_rule(wm: WmHandle, rule: Rule) -> Result<impl warp::Reply, warp::Rejection> {
+    wm.add_rule(rule).await?;
+    Ok(warp::reply::json(&Response::RuleAdded))
+}
+
+async fn handle_remove_rule(
+    name: String,
+    wm: WmHandle,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let removed = wm.remove_rule(name).await?;
+    Ok(warp::reply::json(&Response::RuleRemoved(removed)))
+}
+
+async fn handle_list_scenes(wm: WmHandle) -> Result<impl warp::Reply, warp::Rejection> {
+    let scenes = wm.list_scenes().await?;
+    Ok(warp::reply::json(&Response::Scenes(scenes)))
+}
+
+async fn handle_save_scene(
+    name: String,
+    wm: WmHandle,
+    windows: Option<Vec<Window>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    wm.save_scene(name, windows).await?;
+    Ok(warp::reply::json(&Response::SceneSaved))
+}
+
+async fn handle_apply_scene(
+    name: String,
+    wm: WmHandle,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let applied = wm.apply_scene(name).await?;
+    Ok(warp::reply::json(&Response::SceneApplied(applied)))
+}
+
+async fn handle_delete_scene(
+    name: String,
+    wm: WmHandle,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let deleted = wm.delete_scene(name).await?;
+    Ok(warp::reply::json(&Response::SceneDeleted(deleted)))
+}
+
+async fn handle_apply_layout(
+    wm: WmHandle,
+    windows: Vec<WinLayout>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let report = wm.apply_layout(windows).await?;
+    Ok(warp::reply::json(&Response::LayoutApplied(report)))
+}
+
+#[derive(Deserialize)]
+struct CommitOptions {
+    #[serde(default)]
+    dry_run: bool,
+}
+
+async fn handle_commit(
+    wm: WmHandle,
+    options: CommitOptions,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if options.dry_run {
+        let requests = wm.dry_run_commit().await?;
+        return Ok(warp::reply::json(&Response::DryRun(requests)));
+    }
+    let report = wm.commit().await?;
+    Ok(warp::reply::json(&Response::CommitComplete(report)))
+}
+
+async fn handle_get_pending_changes(wm: WmHandle) -> Result<impl warp::Reply, warp::Rejection> {
+    let changes = wm.get_pending_changes().await?;
+    Ok(warp::reply::json(&Response::PendingChanges(changes)))
+}
+
+async fn handle_discard_pending_changes(wm: WmHandle) -> Result<impl warp::Reply, warp::Rejection> {
+    wm.discard_pending_changes().await?;
+    Ok(warp::reply::json(&Response::PendingChangesDiscarded))
+}
+
+async fn handle_list_history(wm: WmHandle) -> Result<impl warp::Reply, warp::Rejection> {
+    let history = wm.list_history().await?;
+    Ok(warp::reply::json(&Response::History(history)))
+}
+
+async fn handle_undo(wm: WmHandle) -> Result<impl warp::Reply, warp::Rejection> {
+    let restored = wm.undo().await?;
+    Ok(warp::reply::json(&Response::Undone(restored)))
+}
+
+async fn handle_redo(wm: WmHandle) -> Result<impl warp::Reply, warp::Rejection> {
+    let restored = wm.redo().await?;
+    Ok(warp::reply::json(&Response::Redone(restored)))
+}
+
+// answer errors of the window manager with their message, leaving other rejections to warp
+async fn handle_rejection(rejection: warp::Rejection) -> Result<impl warp::Reply, warp::Rejection> {
+    let err = match rejection.find::<Error>() {
+        Some(err) => err,
+        None => return Err(rejection),
+    };
+
+    let msg = err.to_string();
+    let (error, status) = if err.is_invalid_request() {
+        (ErrorType::InvalidInput(msg), StatusCode::BAD_REQUEST)
+    } else {
+        (
+            ErrorType::InternalError(msg),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )
+    };
+    let body = warp::reply::json(&ResponseBody::Error(error));
+    Ok(warp::reply::with_status(body, status))
 }
 
 fn with_wm(
-    wm: Arc<Mutex<WindowManager>>,
-) -> impl Filter<Extract = (Arc<Mutex<WindowManager>>,), Error = std::convert::Infallible> + Clone {
+    wm: WmHandle,
+) -> impl Filter<Extract = (WmHandle,), Error = std::convert::Infallible> + Clone {
     warp::any().map(move || wm.clone())
 }
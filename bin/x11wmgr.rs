@@ -1,11 +1,16 @@
+use std::env;
+use std::path::PathBuf;
 use std::sync::mpsc::channel;
 
 use std::process::exit;
 
 use x11wmgr::messages::{Request, Response};
+use x11wmgr::rules::{default_rules_path, RuleSet};
+use x11wmgr::scenes::{default_scenes_path, SceneStore};
 use x11wmgr::*;
 
 fn main() {
+    #[allow(clippy::iter_nth_zero)]
     let name = std::env::args().nth(0).unwrap_or_default();
     if let Err(err) = run() {
         if name.is_empty() {
@@ -20,22 +25,76 @@ fn main() {
 
 fn run() -> Result<(), Error> {
     let mut wm = WindowManager::new()?;
+    wm.set_rules(load_rules()?);
+    wm.set_scenes(open_scenes()?);
+    wm.set_remember_withdrawn(!flag_arg("--forget-withdrawn"));
 
-    let waker = wm.create_waker()?;
+    let waker = wm.create_waker();
 
-    let (tx_req, rx_req) = channel::<Request>();
-    let tx_resp = create_cli(waker, tx_req);
+    let (tx_req, rx_req) = channel::<PendingRequest>();
+    // the socket is removed once this goes out of scope, i.e. when the window manager stops
+    let _socket =
+        create_socket_listener(socket_path(), waker.clone(), tx_req.clone(), wm.subscribe())?;
+    create_cli(waker, tx_req, wm.subscribe());
 
     loop {
         wm.process_events()?;
 
-        if let Ok(req) = rx_req.try_recv() {
-            let resp = handle_request(&mut wm, req)?;
-            tx_resp.send(resp)?;
+        // serve every request that came in while waiting, not just the one that woke us up
+        while let Ok((req, tx_resp)) = rx_req.try_recv() {
+            let resp = handle_request(&mut wm, req);
+            // a failed request is reported back, unless the window manager cannot go on
+            if let Err(err) = &resp {
+                if err.is_fatal() {
+                    return Err(err.clone());
+                }
+            }
+            // the client may have gone away in the meantime
+            let _ = tx_resp.send(resp);
         }
     }
 }
 
+// path of the control socket, given by `--socket <path>` or the default
+fn socket_path() -> PathBuf {
+    path_arg("--socket").unwrap_or_else(default_socket_path)
+}
+
+// rules from the file given by `--rules <path>`, or from the default file if there is one
+fn load_rules() -> Result<RuleSet, Error> {
+    match path_arg("--rules") {
+        Some(path) => RuleSet::load(path),
+        None => match default_rules_path() {
+            Some(path) => RuleSet::load_if_exists(path),
+            None => Ok(RuleSet::new()),
+        },
+    }
+}
+
+// scenes persisted to the file given by `--scenes <path>` or the default, if there is one
+fn open_scenes() -> Result<SceneStore, Error> {
+    match path_arg("--scenes").or_else(default_scenes_path) {
+        Some(path) => SceneStore::open(path),
+        None => Ok(SceneStore::new()),
+    }
+}
+
+// whether the given command line flag is present
+fn flag_arg(name: &str) -> bool {
+    env::args().skip(1).any(|arg| arg == name)
+}
+
+// value of a `<name> <path>` command line option
+fn path_arg(name: &str) -> Option<PathBuf> {
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == name {
+            return args.next().map(PathBuf::from);
+        }
+    }
+    None
+}
+
 fn handle_request(wm: &mut WindowManager, req: Request) -> Result<Response, Error> {
     let resp = match req {
         Request::ChangeVisibility(win_vis) => {
@@ -58,9 +117,13 @@ fn handle_request(wm: &mut WindowManager, req: Request) -> Result<Response, Erro
             let wins = wm.get_hidden_wins();
             Response::HiddenWindows(wins)
         }
-        Request::Commit => {
-            wm.commit()?;
-            Response::CommitComplete
+        Request::Commit { dry_run: true } => {
+            let requests = wm.dry_run_commit();
+            Response::DryRun(requests)
+        }
+        Request::Commit { dry_run: false } => {
+            let report = wm.commit()?;
+            Response::CommitComplete(report)
         }
         Request::FocusWindow(id) => {
             let is_focused = wm.focus_window(id)?;
@@ -74,6 +137,116 @@ fn handle_request(wm: &mut WindowManager, req: Request) -> Result<Response, Erro
             wm.move_windows(windows.into_iter())?;
             Response::MoveComplete
         }
+        Request::CloseWindows(windows) => {
+            let closed = wm.close_windows(windows.into_iter())?;
+            Response::WindowsClosed(closed)
+        }
+        Request::KillClients(windows) => {
+            let killed = wm.kill_clients(windows.into_iter())?;
+            Response::WindowsClosed(killed)
+        }
+        Request::ListOutputs => {
+            let outputs = wm.list_outputs();
+            Response::Outputs(outputs)
+        }
+        Request::SetLayout(spec) => {
+            wm.set_layout(spec)?;
+            Response::LayoutSet
+        }
+        Request::SwitchWorkspace(name) => {
+            wm.switch_workspace(&name)?;
+            Response::WorkspaceSwitched
+        }
+        Request::MoveToWorkspace(windows) => {
+            let moved = wm.move_to_workspace(windows.into_iter())?;
+            Response::MovedToWorkspace(moved)
+        }
+        Request::ListWorkspaces => {
+            let workspaces = wm.list_workspaces();
+            Response::Workspaces(workspaces)
+        }
+        Request::AddRule(rule) => {
+            wm.add_rule(*rule)?;
+            Response::RuleAdded
+        }
+        Request::RemoveRule(name) => {
+            let removed = wm.remove_rule(&name);
+            Response::RuleRemoved(removed)
+        }
+        Request::ListRules => {
+            let rules = wm.list_rules();
+            Response::Rules(rules)
+        }
+        Request::SaveScene { name, windows } => {
+            wm.save_scene(&name, windows)?;
+            Response::SceneSaved
+        }
+        Request::ApplyScene(name) => {
+            let applied = wm.apply_scene(&name)?;
+            Response::SceneApplied(applied)
+        }
+        Request::ListScenes => {
+            let scenes = wm.list_scenes();
+            Response::Scenes(scenes)
+        }
+        Request::DeleteScene(name) => {
+            let deleted = wm.delete_scene(&name)?;
+            Response::SceneDeleted(deleted)
+        }
+        Request::ApplyLayout(windows) => {
+            let report = wm.apply_layout(windows.into_iter())?;
+            Response::LayoutApplied(report)
+        }
+        Request::GetPendingChanges => {
+            let changes = wm.get_pending_changes();
+            Response::PendingChanges(changes)
+        }
+        Request::DiscardPendingChanges => {
+            wm.discard_pending_changes();
+            Response::PendingChangesDiscarded
+        }
+        Request::Undo => {
+            let restored = wm.undo()?;
+            Response::Undone(restored)
+        }
+        Request::Redo => {
+            let restored = wm.redo()?;
+            Response::Redone(restored)
+        }
+        Request::ListHistory => {
+            let history = wm.list_history();
+            Response::History(history)
+        }
+        Request::RaiseWindow(id) => {
+            let restacked = wm.raise_window(id);
+            Response::Restacked(restacked)
+        }
+        Request::LowerWindow(id) => {
+            let restacked = wm.lower_window(id);
+            Response::Restacked(restacked)
+        }
+        Request::StackAbove { id, sibling } => {
+            let restacked = wm.stack_above(id, sibling);
+            Response::Restacked(restacked)
+        }
+        Request::StackBelow { id, sibling } => {
+            let restacked = wm.stack_below(id, sibling);
+            Response::Restacked(restacked)
+        }
+        Request::SetLayer(windows) => {
+            let changed = wm.set_layer(windows.into_iter());
+            Response::LayerSet(changed)
+        }
+        Request::SetConfigurePolicy { id, policy } => {
+            let is_set = wm.set_configure_policy(id, policy);
+            Response::ConfigurePolicySet(is_set)
+        }
+        Request::TileWindows(windows) => {
+            let tiled = wm.tile_windows(windows.into_iter());
+            Response::WindowsTiled(tiled)
+        }
+        // events are forwarded by the frontend, nothing to do here
+        Request::Subscribe => Response::Subscribed,
     };
 
     Ok(resp)
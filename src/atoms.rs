@@ -0,0 +1,65 @@
+x11rb::atom_manager! {
+    /// Atoms of the properties and types that the window manager reads or writes.
+    pub(crate) Atoms: AtomsCookie {
+        UTF8_STRING,
+        WM_PROTOCOLS,
+        WM_DELETE_WINDOW,
+        WM_STATE,
+        WM_CHANGE_STATE,
+        WM_WINDOW_ROLE,
+        WM_CLIENT_MACHINE,
+        _NET_SUPPORTED,
+        _NET_SUPPORTING_WM_CHECK,
+        _NET_CLIENT_LIST,
+        _NET_CLIENT_LIST_STACKING,
+        _NET_ACTIVE_WINDOW,
+        _NET_NUMBER_OF_DESKTOPS,
+        _NET_DESKTOP_NAMES,
+        _NET_CURRENT_DESKTOP,
+        _NET_WM_DESKTOP,
+        _NET_WM_NAME,
+        _NET_WM_PID,
+        _NET_WM_STATE,
+        _NET_WM_STATE_ABOVE,
+        _NET_WM_STATE_BELOW,
+        _NET_WM_WINDOW_TYPE,
+        _NET_WM_WINDOW_TYPE_DESKTOP,
+        _NET_WM_WINDOW_TYPE_DOCK,
+        _NET_WM_WINDOW_TYPE_TOOLBAR,
+        _NET_WM_WINDOW_TYPE_MENU,
+        _NET_WM_WINDOW_TYPE_UTILITY,
+        _NET_WM_WINDOW_TYPE_SPLASH,
+        _NET_WM_WINDOW_TYPE_DIALOG,
+        _NET_WM_WINDOW_TYPE_NOTIFICATION,
+        _NET_WM_WINDOW_TYPE_NORMAL,
+    }
+}
+
+impl Atoms {
+    // short name of a _NET_WM_WINDOW_TYPE_* atom, e.g. "DOCK"
+    pub(crate) fn window_type_name(&self, atom: u32) -> Option<&'static str> {
+        let name = match atom {
+            a if a == self._NET_WM_WINDOW_TYPE_DESKTOP => "DESKTOP",
+            a if a == self._NET_WM_WINDOW_TYPE_DOCK => "DOCK",
+            a if a == self._NET_WM_WINDOW_TYPE_TOOLBAR => "TOOLBAR",
+            a if a == self._NET_WM_WINDOW_TYPE_MENU => "MENU",
+            a if a == self._NET_WM_WINDOW_TYPE_UTILITY => "UTILITY",
+            a if a == self._NET_WM_WINDOW_TYPE_SPLASH => "SPLASH",
+            a if a == self._NET_WM_WINDOW_TYPE_DIALOG => "DIALOG",
+            a if a == self._NET_WM_WINDOW_TYPE_NOTIFICATION => "NOTIFICATION",
+            a if a == self._NET_WM_WINDOW_TYPE_NORMAL => "NORMAL",
+            _ => return None,
+        };
+        Some(name)
+    }
+
+    // short name of a _NET_WM_STATE_* atom the window manager acts upon, e.g. "ABOVE"
+    pub(crate) fn window_state_name(&self, atom: u32) -> Option<&'static str> {
+        let name = match atom {
+            a if a == self._NET_WM_STATE_ABOVE => "ABOVE",
+            a if a == self._NET_WM_STATE_BELOW => "BELOW",
+            _ => return None,
+        };
+        Some(name)
+    }
+}
@@ -1,65 +1,122 @@
-use std::io;
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
 use std::thread;
 
-use serde::{Deserialize, Serialize};
-use serde_json::{de, ser};
+use serde_json::{de, ser, Value};
 
 use crate::error::*;
 use crate::windowmanager::Waker;
-use crate::messages::{Request, Response};
+use crate::messages::{
+    ErrorType, Event, Input, Request, RequestEnvelope, Response, ResponseBody, ResponseEnvelope,
+};
 
-#[derive(Serialize, Deserialize, Debug)]
-enum ErrorType {
-    InvalidInput(String),
-    InternalError(String),
-}
+/// A request along with the channel on which its response, or the error serving it, is to be sent.
+pub type PendingRequest = (Request, Sender<Result<Response, Error>>);
+
+pub(crate) type Output = Arc<Mutex<dyn Write + Send>>;
 
-#[derive(Serialize, Deserialize, Debug)]
-enum ResponseEnvelope {
-    Error(ErrorType),
-    Result(Response),
+pub fn create_cli(waker: Waker, tx_req: Sender<PendingRequest>, events: Receiver<Event>) {
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        let stdout: Output = Arc::new(Mutex::new(io::stdout()));
+        serve_client(stdin.lock(), stdout, waker, tx_req, events);
+    });
 }
 
-pub fn create_cli(waker: Waker, tx_req: Sender<Request>) -> Sender<Response> {
-    let (tx_resp, rx_resp) = channel::<Response>();
+// Speaks the newline delimited JSON protocol with a single client until its input ends.
+pub(crate) fn serve_client<R: BufRead>(
+    mut input: R,
+    output: Output,
+    waker: Waker,
+    tx_req: Sender<PendingRequest>,
+    events: Receiver<Event>,
+) {
+    // events are only written out once the client has asked for them
+    let subscribed = Arc::new(AtomicBool::new(false));
+    let closed = Arc::new(AtomicBool::new(false));
 
-    thread::spawn(move || {
-        let mut line = String::new();
+    {
+        let subscribed = subscribed.clone();
+        let closed = closed.clone();
+        let output = output.clone();
+        thread::spawn(move || {
+            for event in events {
+                if closed.load(Ordering::Relaxed) {
+                    break;
+                }
+                if subscribed.load(Ordering::Relaxed)
+                    && write_envelope(&output, None, ResponseBody::Event(event)).is_err()
+                {
+                    break;
+                }
+            }
+        });
+    }
 
-        while io::stdin().read_line(&mut line).is_ok() {
-            if let Ok(req) = de::from_str::<Request>(&line) {
-                match handle_input(req, &tx_req, &waker, &rx_resp) {
-                    Ok(resp) => {
-                        let resp = ser::to_string(&ResponseEnvelope::Result(resp)).unwrap();
-                        println!("{}", resp);
-                    }
-                    Err(err) => {
-                        let msg = err.to_string();
-                        let resp = ResponseEnvelope::Error(ErrorType::InternalError(msg));
-                        eprintln!("{}", ser::to_string(&resp).unwrap());
+    let (tx_resp, rx_resp) = channel::<Result<Response, Error>>();
+    let mut line = String::new();
+
+    while let Ok(n) = input.read_line(&mut line) {
+        if n == 0 {
+            break;
+        }
+
+        let written = if let Ok(input) = de::from_str::<Input>(&line) {
+            let RequestEnvelope { id, request: req } = input.into();
+            let is_subscribe = req == Request::Subscribe;
+            match handle_input(req, &tx_req, &waker, &tx_resp, &rx_resp) {
+                Ok(resp) => {
+                    if is_subscribe {
+                        subscribed.store(true, Ordering::Relaxed);
                     }
+                    write_envelope(&output, id, ResponseBody::Result(resp))
+                }
+                Err(err) => {
+                    let msg = err.to_string();
+                    let error = if err.is_invalid_request() {
+                        ErrorType::InvalidInput(msg)
+                    } else {
+                        ErrorType::InternalError(msg)
+                    };
+                    write_envelope(&output, id, ResponseBody::Error(error))
                 }
-            } else {
-                let msg = line.trim().to_owned();
-                let resp = ResponseEnvelope::Error(ErrorType::InvalidInput(msg));
-                eprintln!("{}", ser::to_string(&resp).unwrap());
             }
-            line.clear();
+        } else {
+            // still try to correlate the error with the request it belongs to
+            let id = de::from_str::<Value>(&line)
+                .ok()
+                .and_then(|value| value.get("id").cloned());
+            let msg = line.trim().to_owned();
+            let body = ResponseBody::Error(ErrorType::InvalidInput(msg));
+            write_envelope(&output, id, body)
+        };
+
+        if written.is_err() {
+            break;
         }
-    });
+        line.clear();
+    }
+
+    closed.store(true, Ordering::Relaxed);
+}
 
-    tx_resp
+fn write_envelope(output: &Output, id: Option<Value>, body: ResponseBody) -> io::Result<()> {
+    let resp = ResponseEnvelope { id, body };
+    let mut output = output.lock().unwrap();
+    writeln!(output, "{}", ser::to_string(&resp).unwrap())?;
+    output.flush()
 }
 
 fn handle_input(
     req: Request,
-    tx_req: &Sender<Request>,
+    tx_req: &Sender<PendingRequest>,
     waker: &Waker,
-    rx_resp: &Receiver<Response>,
+    tx_resp: &Sender<Result<Response, Error>>,
+    rx_resp: &Receiver<Result<Response, Error>>,
 ) -> Result<Response, Error> {
-    tx_req.send(req)?;
+    tx_req.send((req, tx_resp.clone()))?;
     waker.wake()?; // wake up wm thread, notifying it of pending input
-    let resp = rx_resp.recv()?;
-    Ok(resp)
+    rx_resp.recv()?
 }
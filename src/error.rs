@@ -9,10 +9,12 @@ use x11rb::errors::ConnectError;
 use x11rb::errors::ConnectionError;
 use x11rb::errors::ParseError;
 use x11rb::errors::ReplyError;
-use x11rb::protocol::xproto::ACCESS_ERROR;
+use x11rb::protocol::xproto::{Window, ACCESS_ERROR};
+use x11rb::protocol::ErrorKind as X11ErrorKind;
 use x11rb::x11_utils::X11Error;
 
-use crate::messages::{Request, Response};
+use crate::cli::PendingRequest;
+use crate::messages::Response;
 
 #[derive(Debug, Error)]
 pub enum ErrorKind {
@@ -25,7 +27,13 @@ pub enum ErrorKind {
     #[error("Resource exhausted: {0}")]
     ResourceExhausted(&'static str),
 
-    #[error("An X11 error occurred")]
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+
+    #[error("Invalid configuration: {0}")]
+    ConfigError(String),
+
+    #[error("X11 error {} ({:?}) in {}", .0.error_code, .0.error_kind, request_name(.0))]
     X11Error(X11Error),
 
     #[error("An IO error occurred")]
@@ -38,15 +46,85 @@ pub enum ErrorKind {
     SendResponseError(#[from] SendError<Response>),
 
     #[error("An internal error occurred")]
-    SendRequestError(#[from] SendError<Request>),
+    SendRequestError(#[from] SendError<PendingRequest>),
 
     #[error("An internal error occurred")]
     RecvError(#[from] RecvError),
+
+    #[error("The window manager has stopped")]
+    Stopped,
+}
+
+// the name of the request an X11 error is about, or its opcodes if it is unknown
+fn request_name(err: &X11Error) -> String {
+    match err.request_name {
+        Some(name) => name.to_owned(),
+        None => format!("request {}.{}", err.major_opcode, err.minor_opcode),
+    }
+}
+
+/// How far the effects of an error reach, which decides whether the window manager carries on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// A request on a single window failed, most likely because the window is gone.
+    Window,
+    /// The request or event at hand could not be dealt with, but nothing else is affected.
+    Request,
+    /// The connection to the server is lost or the window manager cannot go on otherwise.
+    Fatal,
 }
 
 #[derive(Debug, Clone)]
 pub struct Error(Arc<ErrorKind>);
 
+impl Error {
+    pub fn severity(&self) -> Severity {
+        match self.0.as_ref() {
+            ErrorKind::X11Error(err) => match err.error_kind {
+                X11ErrorKind::Window | X11ErrorKind::Drawable | X11ErrorKind::Match => {
+                    Severity::Window
+                }
+                _ => Severity::Request,
+            },
+            ErrorKind::InvalidRequest(_)
+            | ErrorKind::ConfigError(_)
+            | ErrorKind::IOError(_)
+            | ErrorKind::ParseError(_) => Severity::Request,
+            ErrorKind::ConnectError(_)
+            | ErrorKind::ConnectionError(_)
+            | ErrorKind::ResourceExhausted(_)
+            | ErrorKind::SendResponseError(_)
+            | ErrorKind::SendRequestError(_)
+            | ErrorKind::RecvError(_)
+            | ErrorKind::Stopped => Severity::Fatal,
+        }
+    }
+
+    pub fn is_fatal(&self) -> bool {
+        self.severity() == Severity::Fatal
+    }
+
+    /// Whether the request itself was at fault, rather than the window manager or the server.
+    pub fn is_invalid_request(&self) -> bool {
+        matches!(self.0.as_ref(), ErrorKind::InvalidRequest(_))
+    }
+
+    /// Returns the window that no longer exists, if that is what the error is about.
+    pub fn vanished_window(&self) -> Option<Window> {
+        match self.0.as_ref() {
+            ErrorKind::X11Error(err)
+                if matches!(
+                    err.error_kind,
+                    X11ErrorKind::Window | X11ErrorKind::Drawable
+                ) =>
+            {
+                Some(err.bad_value)
+            }
+            _ => None,
+        }
+    }
+}
+
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter) -> DisplayResult {
         let ctx = &self.0.as_ref();
@@ -68,6 +146,12 @@ impl std::error::Error for Error {}
 #[cfg(feature = "websrvc")]
 impl warp::reject::Reject for Error {}
 
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Error(Arc::new(kind))
+    }
+}
+
 impl From<IOError> for Error {
     fn from(error: IOError) -> Self {
         Error(Arc::new(ErrorKind::IOError(error)))
@@ -119,8 +203,8 @@ impl From<SendError<Response>> for Error {
     }
 }
 
-impl From<SendError<Request>> for Error {
-    fn from(error: SendError<Request>) -> Self {
+impl From<SendError<PendingRequest>> for Error {
+    fn from(error: SendError<PendingRequest>) -> Self {
         Error(Arc::new(ErrorKind::SendRequestError(error)))
     }
 }
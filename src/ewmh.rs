@@ -0,0 +1,246 @@
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::*;
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+use x11rb::COPY_DEPTH_FROM_PARENT;
+
+use crate::atoms::Atoms;
+use crate::error::*;
+
+const WM_NAME: &str = "x11wmgr";
+
+/// Announces an EWMH compliant window manager on the root window. The supporting WM check window
+/// it creates lives for as long as the connection does, so it goes away with the window manager.
+pub(crate) fn announce_wm(conn: &RustConnection, atoms: &Atoms, root: Window) -> Result<(), Error> {
+    let check_win = conn.generate_id()?;
+
+    conn.create_window(
+        COPY_DEPTH_FROM_PARENT,
+        check_win,
+        root,
+        -1,
+        -1,
+        1,
+        1,
+        0,
+        WindowClass::INPUT_ONLY,
+        0,
+        &CreateWindowAux::new().override_redirect(1),
+    )?;
+
+    for win in [root, check_win] {
+        conn.change_property32(
+            PropMode::REPLACE,
+            win,
+            atoms._NET_SUPPORTING_WM_CHECK,
+            AtomEnum::WINDOW,
+            &[check_win],
+        )?;
+    }
+
+    conn.change_property8(
+        PropMode::REPLACE,
+        check_win,
+        atoms._NET_WM_NAME,
+        atoms.UTF8_STRING,
+        WM_NAME.as_bytes(),
+    )?;
+
+    let supported = [
+        atoms._NET_SUPPORTED,
+        atoms._NET_SUPPORTING_WM_CHECK,
+        atoms._NET_CLIENT_LIST,
+        atoms._NET_CLIENT_LIST_STACKING,
+        atoms._NET_ACTIVE_WINDOW,
+        atoms._NET_NUMBER_OF_DESKTOPS,
+        atoms._NET_DESKTOP_NAMES,
+        atoms._NET_CURRENT_DESKTOP,
+        atoms._NET_WM_DESKTOP,
+        atoms._NET_WM_NAME,
+        atoms._NET_WM_PID,
+        atoms._NET_WM_WINDOW_TYPE,
+        atoms._NET_WM_STATE,
+        atoms._NET_WM_STATE_ABOVE,
+        atoms._NET_WM_STATE_BELOW,
+    ];
+
+    conn.change_property32(
+        PropMode::REPLACE,
+        root,
+        atoms._NET_SUPPORTED,
+        AtomEnum::ATOM,
+        &supported,
+    )?;
+
+    conn.flush()?;
+
+    Ok(())
+}
+
+// states of WM_STATE and WM_CHANGE_STATE as defined by ICCCM
+pub(crate) const NORMAL_STATE: u32 = 1;
+pub(crate) const ICONIC_STATE: u32 = 3;
+
+// actions of a _NET_WM_STATE client message
+const NET_WM_STATE_REMOVE: u32 = 0;
+const NET_WM_STATE_ADD: u32 = 1;
+const NET_WM_STATE_TOGGLE: u32 = 2;
+
+/// Applies a _NET_WM_STATE client message to the window's _NET_WM_STATE property. Only the states
+/// the window manager acts upon are changed, any others are left as they are.
+pub(crate) fn change_window_state(
+    conn: &RustConnection,
+    atoms: &Atoms,
+    win: Window,
+    action: u32,
+    states: [Atom; 2],
+) -> Result<(), Error> {
+    let mut current = conn
+        .get_property(false, win, atoms._NET_WM_STATE, AtomEnum::ATOM, 0, u32::MAX)?
+        .reply()?
+        .value32()
+        .map(|atoms| atoms.collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    for state in states {
+        if atoms.window_state_name(state).is_none() {
+            continue;
+        }
+        let present = current.contains(&state);
+        let wanted = match action {
+            NET_WM_STATE_REMOVE => false,
+            NET_WM_STATE_ADD => true,
+            NET_WM_STATE_TOGGLE => !present,
+            _ => return Ok(()),
+        };
+        if wanted && !present {
+            current.push(state);
+        } else if !wanted {
+            current.retain(|&atom| atom != state);
+        }
+    }
+
+    conn.change_property32(
+        PropMode::REPLACE,
+        win,
+        atoms._NET_WM_STATE,
+        AtomEnum::ATOM,
+        &current,
+    )?;
+
+    Ok(())
+}
+
+/// Publishes the managed windows, once in mapping order and once in bottom-to-top stacking order.
+pub(crate) fn set_client_lists(
+    conn: &RustConnection,
+    atoms: &Atoms,
+    root: Window,
+    clients: &[Window],
+    stacking: &[Window],
+) -> Result<(), Error> {
+    conn.change_property32(
+        PropMode::REPLACE,
+        root,
+        atoms._NET_CLIENT_LIST,
+        AtomEnum::WINDOW,
+        clients,
+    )?;
+    conn.change_property32(
+        PropMode::REPLACE,
+        root,
+        atoms._NET_CLIENT_LIST_STACKING,
+        AtomEnum::WINDOW,
+        stacking,
+    )?;
+    Ok(())
+}
+
+pub(crate) fn set_active_window(
+    conn: &RustConnection,
+    atoms: &Atoms,
+    root: Window,
+    win: Window,
+) -> Result<(), Error> {
+    conn.change_property32(
+        PropMode::REPLACE,
+        root,
+        atoms._NET_ACTIVE_WINDOW,
+        AtomEnum::WINDOW,
+        &[win],
+    )?;
+    Ok(())
+}
+
+/// Publishes the workspaces by name along with the index of the current one.
+pub(crate) fn set_desktops(
+    conn: &RustConnection,
+    atoms: &Atoms,
+    root: Window,
+    names: &[String],
+    current: usize,
+) -> Result<(), Error> {
+    conn.change_property32(
+        PropMode::REPLACE,
+        root,
+        atoms._NET_NUMBER_OF_DESKTOPS,
+        AtomEnum::CARDINAL,
+        &[names.len() as u32],
+    )?;
+
+    // a list of null terminated strings
+    let mut names_data = Vec::new();
+    for name in names {
+        names_data.extend_from_slice(name.as_bytes());
+        names_data.push(0);
+    }
+    conn.change_property8(
+        PropMode::REPLACE,
+        root,
+        atoms._NET_DESKTOP_NAMES,
+        atoms.UTF8_STRING,
+        &names_data,
+    )?;
+
+    conn.change_property32(
+        PropMode::REPLACE,
+        root,
+        atoms._NET_CURRENT_DESKTOP,
+        AtomEnum::CARDINAL,
+        &[current as u32],
+    )?;
+    Ok(())
+}
+
+pub(crate) fn set_window_desktop(
+    conn: &RustConnection,
+    atoms: &Atoms,
+    win: Window,
+    desktop: usize,
+) -> Result<(), Error> {
+    conn.change_property32(
+        PropMode::REPLACE,
+        win,
+        atoms._NET_WM_DESKTOP,
+        AtomEnum::CARDINAL,
+        &[desktop as u32],
+    )?;
+    Ok(())
+}
+
+/// Sets the ICCCM WM_STATE of a window, without an icon window.
+pub(crate) fn set_wm_state(
+    conn: &RustConnection,
+    atoms: &Atoms,
+    win: Window,
+    state: u32,
+) -> Result<(), Error> {
+    conn.change_property32(
+        PropMode::REPLACE,
+        win,
+        atoms.WM_STATE,
+        atoms.WM_STATE,
+        &[state, x11rb::NONE],
+    )?;
+    Ok(())
+}
@@ -0,0 +1,296 @@
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+
+use futures_util::Stream;
+use tokio::sync::{oneshot, watch};
+
+use crate::error::*;
+use crate::messages::{
+    CommitReport, ConfigureOp, ConfigurePolicy, Event, HistoryEntry, LayoutSpec, OutputInfo,
+    PendingChanges, Rule, Scene, WinClosed, WinLayer, WinLayout, WinMove, WinResize, WinVisbilty,
+    WinWorkspace, WinZIndex, WindowInfo, WorkspaceInfo,
+};
+use crate::windowmanager::{Waker, Window, WindowManager};
+
+// a call to be made on the window manager's thread
+type Job = Box<dyn FnOnce(&mut WindowManager) + Send>;
+
+/// An async handle on a window manager that runs on a thread of its own, where it handles X11
+/// events and the calls made through its handles in turn. Handles are cheap to clone, and the
+/// thread stops once all of them are dropped or the window manager fails for good.
+#[derive(Clone)]
+pub struct WmHandle {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    // `None` only while being dropped, which lets the window manager's thread see it is the last
+    tx_jobs: Option<Sender<Job>>,
+    waker: Waker,
+    // set once the window manager's thread stops, to the error it stopped with
+    stopped: watch::Receiver<Option<Error>>,
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        self.tx_jobs.take();
+        let _ = self.waker.wake();
+    }
+}
+
+impl WmHandle {
+    /// Moves the window manager onto a thread of its own and returns a handle on it.
+    pub fn spawn(mut wm: WindowManager) -> Self {
+        let waker = wm.create_waker();
+        let (tx_jobs, rx_jobs) = channel::<Job>();
+        let (tx_stopped, rx_stopped) = watch::channel(None);
+
+        thread::spawn(move || {
+            let err = match run_jobs(&mut wm, rx_jobs) {
+                Ok(()) => ErrorKind::Stopped.into(),
+                Err(err) => err,
+            };
+            let _ = tx_stopped.send(Some(err));
+        });
+
+        WmHandle {
+            shared: Arc::new(Shared {
+                tx_jobs: Some(tx_jobs),
+                waker,
+                stopped: rx_stopped,
+            }),
+        }
+    }
+
+    /// Calls `f` with the window manager on its thread and returns the result.
+    pub async fn run<F, R>(&self, f: F) -> Result<R, Error>
+    where
+        F: FnOnce(&mut WindowManager) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        let job: Job = Box::new(move |wm| {
+            // the caller may have given up waiting in the meantime
+            let _ = tx.send(f(wm));
+        });
+
+        // a job that cannot be sent is dropped along with `tx`, which ends the wait below
+        if let Some(tx_jobs) = &self.shared.tx_jobs {
+            if tx_jobs.send(job).is_ok() {
+                self.shared.waker.wake()?;
+            }
+        }
+
+        rx.await.map_err(|_| self.stop_error())
+    }
+
+    /// Waits for the window manager's thread to stop and returns the error it stopped with.
+    pub async fn stopped(&self) -> Error {
+        let mut stopped = self.shared.stopped.clone();
+        loop {
+            if let Some(err) = stopped.borrow().clone() {
+                return err;
+            }
+            if stopped.changed().await.is_err() {
+                return ErrorKind::Stopped.into();
+            }
+        }
+    }
+
+    /// Returns a stream of the window lifecycle events from now on.
+    pub async fn events(&self) -> Result<impl Stream<Item = Event> + Send + 'static, Error> {
+        let rx = self.run(|wm| wm.subscribe_async()).await?;
+
+        Ok(futures_util::stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|event| (event, rx))
+        }))
+    }
+
+    pub async fn check_new(&self) -> Result<Vec<WindowInfo>, Error> {
+        self.run(|wm| wm.check_new()).await
+    }
+
+    pub async fn get_visible_wins(&self) -> Result<Vec<WindowInfo>, Error> {
+        self.run(|wm| wm.get_visible_wins()).await
+    }
+
+    pub async fn get_hidden_wins(&self) -> Result<Vec<WindowInfo>, Error> {
+        self.run(|wm| wm.get_hidden_wins()).await
+    }
+
+    pub async fn change_visiblity(&self, windows: Vec<WinVisbilty>) -> Result<Vec<Window>, Error> {
+        self.run(|wm| wm.change_visiblity(windows.into_iter()))
+            .await
+    }
+
+    pub async fn change_indices(&self, windows: Vec<WinZIndex>) -> Result<Vec<Window>, Error> {
+        self.run(|wm| wm.change_indices(windows.into_iter())).await
+    }
+
+    pub async fn raise_window(&self, id: Window) -> Result<bool, Error> {
+        self.run(move |wm| wm.raise_window(id)).await
+    }
+
+    pub async fn lower_window(&self, id: Window) -> Result<bool, Error> {
+        self.run(move |wm| wm.lower_window(id)).await
+    }
+
+    pub async fn stack_above(&self, id: Window, sibling: Window) -> Result<bool, Error> {
+        self.run(move |wm| wm.stack_above(id, sibling)).await
+    }
+
+    pub async fn stack_below(&self, id: Window, sibling: Window) -> Result<bool, Error> {
+        self.run(move |wm| wm.stack_below(id, sibling)).await
+    }
+
+    pub async fn set_layer(&self, windows: Vec<WinLayer>) -> Result<Vec<Window>, Error> {
+        self.run(|wm| wm.set_layer(windows.into_iter())).await
+    }
+
+    pub async fn tile_windows(&self, windows: Vec<Window>) -> Result<Vec<Window>, Error> {
+        self.run(|wm| wm.tile_windows(windows.into_iter())).await
+    }
+
+    pub async fn list_outputs(&self) -> Result<Vec<OutputInfo>, Error> {
+        self.run(|wm| wm.list_outputs()).await
+    }
+
+    pub async fn focus_window(&self, id: Window) -> Result<bool, Error> {
+        self.run(move |wm| wm.focus_window(id)).await?
+    }
+
+    pub async fn close_windows(&self, windows: Vec<Window>) -> Result<WinClosed, Error> {
+        self.run(|wm| wm.close_windows(windows.into_iter())).await?
+    }
+
+    pub async fn kill_clients(&self, windows: Vec<Window>) -> Result<WinClosed, Error> {
+        self.run(|wm| wm.kill_clients(windows.into_iter())).await?
+    }
+
+    pub async fn resize_windows(&self, windows: Vec<WinResize>) -> Result<(), Error> {
+        self.run(|wm| wm.resize_windows(windows.into_iter()))
+            .await?
+    }
+
+    pub async fn move_windows(&self, windows: Vec<WinMove>) -> Result<(), Error> {
+        self.run(|wm| wm.move_windows(windows.into_iter())).await?
+    }
+
+    pub async fn set_layout(&self, spec: Option<LayoutSpec>) -> Result<(), Error> {
+        self.run(|wm| wm.set_layout(spec)).await?
+    }
+
+    pub async fn set_configure_policy(
+        &self,
+        id: Option<Window>,
+        policy: Option<ConfigurePolicy>,
+    ) -> Result<bool, Error> {
+        self.run(move |wm| wm.set_configure_policy(id, policy))
+            .await
+    }
+
+    pub async fn switch_workspace(&self, name: String) -> Result<(), Error> {
+        self.run(move |wm| wm.switch_workspace(&name)).await?
+    }
+
+    pub async fn move_to_workspace(
+        &self,
+        windows: Vec<WinWorkspace>,
+    ) -> Result<Vec<Window>, Error> {
+        self.run(|wm| wm.move_to_workspace(windows.into_iter()))
+            .await?
+    }
+
+    pub async fn list_workspaces(&self) -> Result<Vec<WorkspaceInfo>, Error> {
+        self.run(|wm| wm.list_workspaces()).await
+    }
+
+    pub async fn add_rule(&self, rule: Rule) -> Result<(), Error> {
+        self.run(|wm| wm.add_rule(rule)).await?
+    }
+
+    pub async fn remove_rule(&self, name: String) -> Result<bool, Error> {
+        self.run(move |wm| wm.remove_rule(&name)).await
+    }
+
+    pub async fn list_rules(&self) -> Result<Vec<Rule>, Error> {
+        self.run(|wm| wm.list_rules()).await
+    }
+
+    pub async fn save_scene(
+        &self,
+        name: String,
+        windows: Option<Vec<Window>>,
+    ) -> Result<(), Error> {
+        self.run(move |wm| wm.save_scene(&name, windows)).await?
+    }
+
+    pub async fn apply_scene(&self, name: String) -> Result<Vec<Window>, Error> {
+        self.run(move |wm| wm.apply_scene(&name)).await?
+    }
+
+    pub async fn list_scenes(&self) -> Result<Vec<Scene>, Error> {
+        self.run(|wm| wm.list_scenes()).await
+    }
+
+    pub async fn delete_scene(&self, name: String) -> Result<bool, Error> {
+        self.run(move |wm| wm.delete_scene(&name)).await?
+    }
+
+    pub async fn apply_layout(&self, windows: Vec<WinLayout>) -> Result<CommitReport, Error> {
+        self.run(|wm| wm.apply_layout(windows.into_iter())).await?
+    }
+
+    pub async fn get_pending_changes(&self) -> Result<PendingChanges, Error> {
+        self.run(|wm| wm.get_pending_changes()).await
+    }
+
+    pub async fn discard_pending_changes(&self) -> Result<(), Error> {
+        self.run(|wm| wm.discard_pending_changes()).await
+    }
+
+    pub async fn commit(&self) -> Result<CommitReport, Error> {
+        self.run(|wm| wm.commit()).await?
+    }
+
+    pub async fn dry_run_commit(&self) -> Result<Vec<ConfigureOp>, Error> {
+        self.run(|wm| wm.dry_run_commit()).await
+    }
+
+    pub async fn undo(&self) -> Result<Option<Vec<Window>>, Error> {
+        self.run(|wm| wm.undo()).await?
+    }
+
+    pub async fn redo(&self) -> Result<Option<Vec<Window>>, Error> {
+        self.run(|wm| wm.redo()).await?
+    }
+
+    pub async fn list_history(&self) -> Result<Vec<HistoryEntry>, Error> {
+        self.run(|wm| wm.list_history()).await
+    }
+
+    // the error calls fail with once the window manager's thread is gone
+    fn stop_error(&self) -> Error {
+        self.shared
+            .stopped
+            .borrow()
+            .clone()
+            .unwrap_or_else(|| ErrorKind::Stopped.into())
+    }
+}
+
+// handle X11 events and run jobs as they come in, until every handle is gone
+fn run_jobs(wm: &mut WindowManager, rx_jobs: Receiver<Job>) -> Result<(), Error> {
+    loop {
+        wm.process_events()?;
+
+        loop {
+            match rx_jobs.try_recv() {
+                Ok(job) => job(wm),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Ok(()),
+            }
+        }
+    }
+}
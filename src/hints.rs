@@ -0,0 +1,148 @@
+use std::convert::TryFrom;
+
+use x11rb::properties::WmSizeHints;
+use x11rb::protocol::xproto::{Gravity, Window};
+use x11rb::rust_connection::RustConnection;
+
+use crate::error::*;
+
+/// Constraints a client places on the size of its window through WM_NORMAL_HINTS.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SizeHints {
+    pub min_size: Option<(u32, u32)>,
+    pub max_size: Option<(u32, u32)>,
+    pub base_size: Option<(u32, u32)>,
+    pub increment: Option<(u32, u32)>,
+    // minimum and maximum aspect ratio, each as (numerator, denominator)
+    pub aspect: Option<((u32, u32), (u32, u32))>,
+    pub gravity: Gravity,
+}
+
+impl Default for SizeHints {
+    fn default() -> Self {
+        SizeHints {
+            min_size: None,
+            max_size: None,
+            base_size: None,
+            increment: None,
+            aspect: None,
+            gravity: Gravity::NORTH_WEST,
+        }
+    }
+}
+
+impl From<WmSizeHints> for SizeHints {
+    fn from(hints: WmSizeHints) -> Self {
+        let size = |size: Option<(i32, i32)>| {
+            let (w, h) = size?;
+            Some((u32::try_from(w).ok()?, u32::try_from(h).ok()?))
+        };
+        let ratio = |numerator: i32, denominator: i32| {
+            Some((
+                u32::try_from(numerator).ok()?,
+                u32::try_from(denominator).ok()?,
+            ))
+        };
+
+        SizeHints {
+            min_size: size(hints.min_size),
+            max_size: size(hints.max_size),
+            base_size: size(hints.base_size),
+            increment: size(hints.size_increment),
+            aspect: hints.aspect.and_then(|(min, max)| {
+                Some((
+                    ratio(min.numerator, min.denominator)?,
+                    ratio(max.numerator, max.denominator)?,
+                ))
+            }),
+            gravity: hints.win_gravity.unwrap_or(Gravity::NORTH_WEST),
+        }
+    }
+}
+
+impl SizeHints {
+    /// Returns the size closest to the given one that satisfies the hints. Sizes are only ever
+    /// reduced to meet the aspect ratio and increments, so the window stays within the requested
+    /// area unless that is smaller than the minimum size.
+    pub fn constrain(&self, (width, height): (u32, u32)) -> (u32, u32) {
+        // per ICCCM, the base and minimum size stand in for each other
+        let min = self.min_size.or(self.base_size).unwrap_or((1, 1));
+        let base = self.base_size.or(self.min_size).unwrap_or((0, 0));
+        let max = self.max_size.unwrap_or((u32::MAX, u32::MAX));
+
+        let clamp = |value: u32, min: u32, max: u32| value.min(max).max(min).max(1);
+        let mut w = clamp(width, min.0, max.0);
+        let mut h = clamp(height, min.1, max.1);
+
+        // the aspect ratio applies to the size beyond the base size, if one is given
+        if let Some(((min_num, min_den), (max_num, max_den))) = self.aspect {
+            if min_num > 0 && min_den > 0 && max_num > 0 && max_den > 0 {
+                let (base_w, base_h) = self.base_size.unwrap_or((0, 0));
+                let aw = f64::from(w.saturating_sub(base_w));
+                let ah = f64::from(h.saturating_sub(base_h));
+                let min_ratio = f64::from(min_num) / f64::from(min_den);
+                let max_ratio = f64::from(max_num) / f64::from(max_den);
+
+                if ah > 0.0 && aw / ah > max_ratio {
+                    w = base_w + (ah * max_ratio).round() as u32;
+                } else if ah > 0.0 && aw / ah < min_ratio {
+                    h = base_h + (aw / min_ratio).round() as u32;
+                }
+            }
+        }
+
+        if let Some((inc_w, inc_h)) = self.increment {
+            if inc_w > 0 && w > base.0 {
+                w -= (w - base.0) % inc_w;
+            }
+            if inc_h > 0 && h > base.1 {
+                h -= (h - base.1) % inc_h;
+            }
+        }
+
+        (clamp(w, min.0, max.0), clamp(h, min.1, max.1))
+    }
+
+    /// Returns the position of a window that was given `size` instead of the `requested` size at
+    /// the given position, such that the reference point of its window gravity stays in place.
+    pub fn gravitate(
+        &self,
+        (x, y): (i32, i32),
+        requested: (u32, u32),
+        size: (u32, u32),
+    ) -> (i32, i32) {
+        // offsets of the reference point, in halves of the width and height
+        let (fx, fy) = match self.gravity {
+            Gravity::NORTH => (1, 0),
+            Gravity::NORTH_EAST => (2, 0),
+            Gravity::WEST => (0, 1),
+            Gravity::CENTER => (1, 1),
+            Gravity::EAST => (2, 1),
+            Gravity::SOUTH_WEST => (0, 2),
+            Gravity::SOUTH => (1, 2),
+            Gravity::SOUTH_EAST => (2, 2),
+            _ => (0, 0),
+        };
+
+        let shift = |pos: i32, requested: u32, actual: u32, factor: i64| {
+            let delta = (i64::from(requested) - i64::from(actual)) * factor / 2;
+            i32::try_from(i64::from(pos) + delta).unwrap_or(pos)
+        };
+
+        (
+            shift(x, requested.0, size.0, fx),
+            shift(y, requested.1, size.1, fy),
+        )
+    }
+}
+
+/// Reads the WM_NORMAL_HINTS of a window. Missing or malformed hints place no constraints.
+pub(crate) fn query_size_hints(conn: &RustConnection, win: Window) -> Result<SizeHints, Error> {
+    let hints = WmSizeHints::get_normal_hints(conn, win)?;
+    Ok(hints
+        .reply()
+        .ok()
+        .flatten()
+        .map(SizeHints::from)
+        .unwrap_or_default())
+}
@@ -0,0 +1,74 @@
+use std::collections::VecDeque;
+
+/// A bounded list of states, oldest first, with a position that undo and redo move along.
+/// Once full, the oldest state is dropped to make room for a new one.
+pub struct History<T> {
+    states: VecDeque<T>,
+    // index of the current state, meaningless while there are no states
+    position: usize,
+    capacity: usize,
+}
+
+impl<T> History<T> {
+    /// Creates an empty history keeping at most `capacity` states, but always at least one.
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        History {
+            states: VecDeque::with_capacity(capacity),
+            position: 0,
+            capacity,
+        }
+    }
+
+    /// Records a new current state. States that were undone can no longer be redone.
+    pub fn record(&mut self, state: T) {
+        if !self.states.is_empty() {
+            self.states.truncate(self.position + 1);
+        }
+        if self.states.len() == self.capacity {
+            self.states.pop_front();
+        }
+        self.states.push_back(state);
+        self.position = self.states.len() - 1;
+    }
+
+    /// Steps back to the previous state and returns it, if there is one.
+    pub fn undo(&mut self) -> Option<&T> {
+        if self.position == 0 {
+            return None;
+        }
+        self.position -= 1;
+        self.states.get(self.position)
+    }
+
+    /// Steps forward to the state that was last undone and returns it, if there is one.
+    pub fn redo(&mut self) -> Option<&T> {
+        if self.position + 1 >= self.states.len() {
+            return None;
+        }
+        self.position += 1;
+        self.states.get(self.position)
+    }
+
+    /// Returns the current state, if any state was recorded yet.
+    pub fn current(&self) -> Option<&T> {
+        self.states.get(self.position)
+    }
+
+    /// Iterates over all states, oldest first, along with whether each is the current one.
+    pub fn iter(&self) -> impl Iterator<Item = (bool, &T)> {
+        let position = self.position;
+        self.states
+            .iter()
+            .enumerate()
+            .map(move |(i, state)| (i == position, state))
+    }
+
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+}
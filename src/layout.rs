@@ -0,0 +1,110 @@
+use std::convert::TryFrom;
+
+use crate::messages::Layout;
+
+/// An area of the screen, given as its position and size.
+pub type Area = ((i32, i32), (u32, u32));
+
+/// Divides the area between `count` windows according to the layout, leaving `gaps` pixels
+/// between windows and around the edges. Windows are given in order of importance, the first
+/// one being the master in a master-stack layout.
+pub fn arrange(layout: &Layout, area: Area, gaps: u32, count: usize) -> Vec<Area> {
+    if count == 0 {
+        return Vec::new();
+    }
+
+    let ((x, y), (width, height)) = area;
+    let inner = (
+        (
+            x.saturating_add(offset(gaps)),
+            y.saturating_add(offset(gaps)),
+        ),
+        (
+            width.saturating_sub(gaps.saturating_mul(2)),
+            height.saturating_sub(gaps.saturating_mul(2)),
+        ),
+    );
+
+    match layout {
+        Layout::Monocle => vec![inner; count],
+        Layout::Columns => columns(inner, gaps, count),
+        Layout::Rows => rows(inner, gaps, count),
+        Layout::Grid => grid(inner, gaps, count),
+        Layout::MasterStack { ratio, masters } => {
+            master_stack(inner, gaps, count, *ratio, (*masters).max(1))
+        }
+    }
+}
+
+fn columns(((x, y), (width, height)): Area, gaps: u32, count: usize) -> Vec<Area> {
+    split(x, width, gaps, count)
+        .into_iter()
+        .map(|(x, width)| ((x, y), (width, height)))
+        .collect()
+}
+
+fn rows(((x, y), (width, height)): Area, gaps: u32, count: usize) -> Vec<Area> {
+    split(y, height, gaps, count)
+        .into_iter()
+        .map(|(y, height)| ((x, y), (width, height)))
+        .collect()
+}
+
+fn grid(area: Area, gaps: u32, count: usize) -> Vec<Area> {
+    let ncols = (count as f64).sqrt().ceil() as usize;
+    let nrows = count.div_ceil(ncols);
+
+    let ((x, y), (width, height)) = area;
+    let mut areas = Vec::with_capacity(count);
+
+    for (row, (row_y, row_height)) in split(y, height, gaps, nrows).into_iter().enumerate() {
+        // the last row takes whatever is left, stretching its windows to fill the width
+        let in_row = ncols.min(count - row * ncols);
+        areas.extend(columns(((x, row_y), (width, row_height)), gaps, in_row));
+    }
+
+    areas
+}
+
+fn master_stack(area: Area, gaps: u32, count: usize, ratio: f64, masters: usize) -> Vec<Area> {
+    if count <= masters {
+        return rows(area, gaps, count);
+    }
+
+    let ((x, y), (width, height)) = area;
+    let usable = width.saturating_sub(gaps);
+    let master_width = (f64::from(usable) * ratio.clamp(0.0, 1.0)).round() as u32;
+    let stack_width = usable - master_width;
+
+    let mut areas = rows(((x, y), (master_width, height)), gaps, masters);
+    let stack_x = x.saturating_add(offset(master_width.saturating_add(gaps)));
+    areas.extend(rows(
+        ((stack_x, y), (stack_width, height)),
+        gaps,
+        count - masters,
+    ));
+    areas
+}
+
+// split a span into `count` parts separated by gaps, handing out leftover pixels from the start
+fn split(start: i32, len: u32, gaps: u32, count: usize) -> Vec<(i32, u32)> {
+    let count = count as u32;
+    let usable = len.saturating_sub(gaps.saturating_mul(count - 1));
+    let base = usable / count;
+    let extra = usable % count;
+
+    let mut pos = start;
+    (0..count)
+        .map(|i| {
+            let size = base + if i < extra { 1 } else { 0 };
+            let part = (pos, size);
+            pos = pos.saturating_add(offset(size.saturating_add(gaps)));
+            part
+        })
+        .collect()
+}
+
+// a distance in pixels as an offset to a position, saturating rather than wrapping
+fn offset(px: u32) -> i32 {
+    i32::try_from(px).unwrap_or(i32::MAX)
+}
@@ -1,9 +1,25 @@
+mod atoms;
 mod cli;
 mod error;
+mod ewmh;
+#[cfg(feature = "async")]
+mod handle;
+pub mod hints;
+pub mod history;
+pub mod layout;
 pub mod messages;
+mod outputs;
+mod properties;
+pub mod rules;
+pub mod scenes;
+mod socket;
+pub mod stacking;
 mod windowmanager;
 
 pub use cli::*;
-pub use error::Error;
+pub use error::{Error, Severity};
+#[cfg(feature = "async")]
+pub use handle::WmHandle;
+pub use socket::*;
 pub use windowmanager::Window;
 pub use windowmanager::WindowManager;
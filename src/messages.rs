@@ -1,18 +1,104 @@
+use crate::layout::Area;
 use crate::windowmanager::{Window, ZIndexType};
-use serde::{Serialize, Deserialize};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::convert::TryFrom;
+
+/// A coordinate or dimension, given either in pixels (`100`), as a percentage (`"50%"`) or as a
+/// fraction (`0.5`) of the output or screen it is relative to.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "LengthRepr")]
+pub enum Length {
+    Pixels(i64),
+    Percent(f64),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LengthRepr {
+    Pixels(i64),
+    Fraction(f64),
+    Text(String),
+}
+
+impl TryFrom<LengthRepr> for Length {
+    type Error = String;
+
+    fn try_from(repr: LengthRepr) -> Result<Self, Self::Error> {
+        match repr {
+            LengthRepr::Pixels(px) => Ok(Length::Pixels(px)),
+            LengthRepr::Fraction(fraction) => Ok(Length::Percent(fraction * 100.0)),
+            LengthRepr::Text(text) => {
+                let percent = text
+                    .trim()
+                    .strip_suffix('%')
+                    .and_then(|percent| percent.trim().parse::<f64>().ok());
+                match percent {
+                    Some(percent) => Ok(Length::Percent(percent)),
+                    None => text
+                        .trim()
+                        .parse::<i64>()
+                        .map(Length::Pixels)
+                        .map_err(|_| format!("invalid length: {}", text)),
+                }
+            }
+        }
+    }
+}
+
+impl Serialize for Length {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Length::Pixels(px) => serializer.serialize_i64(*px),
+            Length::Percent(percent) => serializer.serialize_str(&format!("{}%", percent)),
+        }
+    }
+}
+
+impl Length {
+    /// Resolves the length against the given total, returning a number of pixels.
+    pub fn resolve(&self, total: u32) -> i64 {
+        match self {
+            Length::Pixels(px) => *px,
+            Length::Percent(percent) => (f64::from(total) * percent / 100.0).round() as i64,
+        }
+    }
+}
+
+impl From<i32> for Length {
+    fn from(px: i32) -> Self {
+        Length::Pixels(i64::from(px))
+    }
+}
+
+impl From<u32> for Length {
+    fn from(px: u32) -> Self {
+        Length::Pixels(i64::from(px))
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct WinResize {
     pub id: Window,
-    pub width: u32,
-    pub height: u32,
+    // name of the output percentages are relative to, defaults to the whole screen
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub output: Option<String>,
+    pub width: Length,
+    pub height: Length,
+    // apply the size as given, rather than adjusting it to the window's WM_NORMAL_HINTS
+    #[serde(skip_serializing_if = "is_false", default)]
+    pub ignore_hints: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct WinMove {
     pub id: Window,
-    pub x: i32,
-    pub y: i32,
+    // name of the output the position is relative to, defaults to the whole screen
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub output: Option<String>,
+    pub x: Length,
+    pub y: Length,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
@@ -27,7 +113,45 @@ pub struct WinZIndex {
     pub zindex: ZIndexType,
 }
 
+/// The complete target state of a window; fields that are left out stay as they are.
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct WinLayout {
+    pub id: Window,
+    // name of the output the geometry is relative to, defaults to the whole screen
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub output: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub visible: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub zindex: Option<ZIndexType>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub x: Option<Length>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub y: Option<Length>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub width: Option<Length>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub height: Option<Length>,
+    #[serde(skip_serializing_if = "is_false", default)]
+    pub ignore_hints: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct WinLayer {
+    pub id: Window,
+    // `None` goes back to the layer implied by the window's type and state
+    pub layer: Option<StackLayer>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct WinWorkspace {
+    pub id: Window,
+    pub workspace: String,
+}
+
+// (de)serialized through the impls below, which keep accepting a bare "Commit"
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(remote = "Self")]
 pub enum Request {
     ChangeVisibility(Vec<WinVisbilty>),
     ChangeZIndex(Vec<WinZIndex>),
@@ -38,27 +162,603 @@ pub enum Request {
     ListVisibleWindows,
     ListHiddenWindows,
     FocusWindow(Window),
-    Commit,
+    Commit {
+        // only report the requests a commit would issue, without changing anything
+        #[serde(default)]
+        dry_run: bool,
+    },
+    Subscribe,
+    CloseWindows(Vec<Window>),
+    KillClients(Vec<Window>),
+    ListOutputs,
+    SetLayout(Option<LayoutSpec>),
+    SwitchWorkspace(String),
+    MoveToWorkspace(Vec<WinWorkspace>),
+    ListWorkspaces,
+    AddRule(Box<Rule>),
+    RemoveRule(String),
+    ListRules,
+    SaveScene {
+        name: String,
+        // windows to capture, defaults to all windows of the current workspace
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        windows: Option<Vec<Window>>,
+    },
+    ApplyScene(String),
+    ListScenes,
+    DeleteScene(String),
+    ApplyLayout(Vec<WinLayout>),
+    GetPendingChanges,
+    DiscardPendingChanges,
+    Undo,
+    Redo,
+    ListHistory,
+    RaiseWindow(Window),
+    LowerWindow(Window),
+    StackAbove {
+        id: Window,
+        sibling: Window,
+    },
+    StackBelow {
+        id: Window,
+        sibling: Window,
+    },
+    SetLayer(Vec<WinLayer>),
+    SetConfigurePolicy {
+        // window the policy applies to, or all windows without a policy of their own
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        id: Option<Window>,
+        // `None` falls back to the global policy, or resets the global policy to `Allow`
+        policy: Option<ConfigurePolicy>,
+    },
+    TileWindows(Vec<Window>),
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+// index of `Request::Commit`, which a plain commit is serialized as a unit variant with
+const COMMIT_VARIANT_INDEX: u32 = 8;
+
+impl Serialize for Request {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Request::Commit { dry_run: false } => {
+                serializer.serialize_unit_variant("Request", COMMIT_VARIANT_INDEX, "Commit")
+            }
+            _ => Request::serialize(self, serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Request {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        if value == "Commit" {
+            return Ok(Request::Commit { dry_run: false });
+        }
+        Request::deserialize(value).map_err(D::Error::custom)
+    }
+}
+
+/// Metadata read from the properties of a client window.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct WindowProperties {
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub class: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub instance: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub pid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub role: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub client_machine: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub window_type: Vec<String>,
+    // states of _NET_WM_STATE that affect stacking, i.e. "ABOVE" and "BELOW"
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub window_state: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct WindowInfo {
     pub id: Window,
     pub x: i32,
     pub y: i32,
     pub width: u32,
     pub height: u32,
+    #[serde(flatten)]
+    pub properties: WindowProperties,
 }
 
+// (de)serialized through the impls below, which keep a commit or applied layout with nothing to
+// report a bare "CommitComplete" or "LayoutApplied"
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(remote = "Self")]
 pub enum Response {
     VisibiltyChanged(Vec<Window>),
     ZIndexChanged(Vec<Window>),
     NewWindows(Vec<WindowInfo>),
     VisibleWindows(Vec<WindowInfo>),
     HiddenWindows(Vec<WindowInfo>),
-    CommitComplete,
+    CommitComplete(CommitReport),
     MoveComplete,
     ResizeComplete,
     WindowFocused(bool),
+    Subscribed,
+    WindowsClosed(WinClosed),
+    Outputs(Vec<OutputInfo>),
+    LayoutSet,
+    WorkspaceSwitched,
+    MovedToWorkspace(Vec<Window>),
+    Workspaces(Vec<WorkspaceInfo>),
+    RuleAdded,
+    RuleRemoved(bool),
+    Rules(Vec<Rule>),
+    SceneSaved,
+    SceneApplied(Vec<Window>),
+    Scenes(Vec<Scene>),
+    SceneDeleted(bool),
+    // like `CommitComplete`, for the commit that applies the layout
+    LayoutApplied(CommitReport),
+    PendingChanges(PendingChanges),
+    PendingChangesDiscarded,
+    DryRun(Vec<ConfigureOp>),
+    // windows that were restored, or `None` if there was nothing to undo or redo
+    Undone(Option<Vec<Window>>),
+    Redone(Option<Vec<Window>>),
+    History(Vec<HistoryEntry>),
+    Restacked(bool),
+    LayerSet(Vec<Window>),
+    ConfigurePolicySet(bool),
+    WindowsTiled(Vec<Window>),
+}
+
+// indices of `Response::CommitComplete` and `Response::LayoutApplied`, which are serialized as
+// unit variants with when there is nothing to report
+const COMMIT_COMPLETE_VARIANT_INDEX: u32 = 5;
+const LAYOUT_APPLIED_VARIANT_INDEX: u32 = 24;
+
+impl Serialize for Response {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Response::CommitComplete(report) if report.is_empty() => serializer
+                .serialize_unit_variant(
+                    "Response",
+                    COMMIT_COMPLETE_VARIANT_INDEX,
+                    "CommitComplete",
+                ),
+            Response::LayoutApplied(report) if report.is_empty() => serializer
+                .serialize_unit_variant("Response", LAYOUT_APPLIED_VARIANT_INDEX, "LayoutApplied"),
+            _ => Response::serialize(self, serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Response {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        if value == "CommitComplete" {
+            return Ok(Response::CommitComplete(CommitReport::default()));
+        }
+        if value == "LayoutApplied" {
+            return Ok(Response::LayoutApplied(CommitReport::default()));
+        }
+        Response::deserialize(value).map_err(D::Error::custom)
+    }
+}
+
+/// What a commit did: the windows it could not configure, along with the X11 error each failed
+/// with, and the geometry the server applied to the windows it moved or resized.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct CommitReport {
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub failures: Vec<WinFailure>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub applied: Vec<WinGeometry>,
+}
+
+impl CommitReport {
+    pub fn is_empty(&self) -> bool {
+        self.failures.is_empty() && self.applied.is_empty()
+    }
+}
+
+/// A window that a request could not be carried out on.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WinFailure {
+    pub id: Window,
+    pub error: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Layout {
+    // `ratio` is the share of the width given to the master windows
+    MasterStack {
+        ratio: f64,
+        #[serde(default = "default_masters")]
+        masters: usize,
+    },
+    Grid,
+    Columns,
+    Rows,
+    Monocle,
+}
+
+fn default_masters() -> usize {
+    1
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// Tiles the visible windows on every commit, ordered by z-index with the highest first.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LayoutSpec {
+    pub layout: Layout,
+    // pixels between windows and around the edges
+    #[serde(default)]
+    pub gaps: u32,
+    // name of the output to tile, defaults to the whole screen
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub output: Option<String>,
+    // leave windows that were explicitly moved or resized where they are
+    #[serde(default)]
+    pub float_manual: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct WinClosed {
+    // windows that were sent WM_DELETE_WINDOW
+    pub asked: Vec<Window>,
+    // windows whose clients were killed
+    pub killed: Vec<Window>,
+    // windows that could not be closed, e.g. because they vanished in the meantime
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub failures: Vec<WinFailure>,
+}
+
+/// A workspace along with its windows, each list ordered by z-index from lowest to highest.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WorkspaceInfo {
+    pub name: String,
+    pub current: bool,
+    pub visible: Vec<Window>,
+    pub hidden: Vec<Window>,
+}
+
+/// Criteria a newly mapped window has to meet for a rule to apply. Every given criterion must
+/// match; a rule without any criteria applies to all windows.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct RuleMatch {
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub class: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub instance: Option<String>,
+    // regular expression searched for in the title
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub role: Option<String>,
+    // short name of a _NET_WM_WINDOW_TYPE, e.g. "DIALOG"
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub window_type: Option<String>,
+    // pid of the client or of any of its parent processes
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub ancestor_pid: Option<u32>,
+    // command name of the client or of any of its parent processes
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub ancestor: Option<String>,
+}
+
+/// What to do with a window matched by a rule. When several rules match, they are applied in
+/// order, so later rules override what earlier ones set.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct RuleActions {
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub visible: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub zindex: Option<ZIndexType>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub workspace: Option<String>,
+    // name of the output the geometry is relative to, defaults to the whole screen
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub output: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub x: Option<Length>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub y: Option<Length>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub width: Option<Length>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub height: Option<Length>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub focus: Option<bool>,
+}
+
+/// A named rule consulted whenever a window is mapped.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub name: String,
+    #[serde(rename = "match", default)]
+    pub criteria: RuleMatch,
+    #[serde(default)]
+    pub actions: RuleActions,
+}
+
+/// The state of a single window within a scene. Windows are identified by their class and title
+/// rather than their id, so that scenes survive restarts of the clients.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SceneWindow {
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub class: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub instance: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub title: Option<String>,
+    pub visible: bool,
+    pub zindex: ZIndexType,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A named arrangement of windows that can be applied again later.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Scene {
+    pub name: String,
+    pub windows: Vec<SceneWindow>,
+}
+
+/// A value as last committed and as it would be after the next commit.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Change<T> {
+    pub from: T,
+    pub to: T,
+}
+
+/// What the next commit would change about a window. Fields that stay the same are left out.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WinChanges {
+    pub id: Window,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub visible: Option<Change<bool>>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub zindex: Option<Change<ZIndexType>>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub workspace: Option<Change<String>>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub layer: Option<Change<StackLayer>>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub x: Option<Change<i32>>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub y: Option<Change<i32>>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub width: Option<Change<u32>>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub height: Option<Change<u32>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PendingChanges {
+    // switch of the current workspace, if any
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub workspace: Option<Change<String>>,
+    pub windows: Vec<WinChanges>,
+}
+
+/// The state of a single window as of a commit.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WinSnapshot {
+    pub id: Window,
+    pub visible: bool,
+    pub zindex: ZIndexType,
+    pub workspace: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// The state of all windows as of a commit, as kept for undo and redo.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    // seconds since the Unix epoch
+    pub time: u64,
+    // the entry undo and redo move away from, i.e. the state currently committed
+    pub current: bool,
+    pub workspace: String,
+    pub windows: Vec<WinSnapshot>,
+}
+
+/// Layers that visible windows are stacked in, from bottom to top. Within a layer, windows are
+/// ordered by z-index.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum StackLayer {
+    Below,
+    Normal,
+    Above,
+    Overlay,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum StackPosition {
+    Above,
+    Below,
+}
+
+/// The geometry of a window as applied by a commit.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WinGeometry {
+    pub id: Window,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A ConfigureWindow request issued by a commit. Geometry that does not change is left out.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ConfigureOp {
+    pub id: Window,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub x: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub y: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub height: Option<u32>,
+    pub stack_mode: StackPosition,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WinConfigure {
+    pub id: Window,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub x: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub y: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub height: Option<u32>,
+}
+
+/// How ConfigureRequests of clients are dealt with.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub enum ConfigurePolicy {
+    // grant the request as is
+    #[default]
+    Allow,
+    // refuse the request, the client is told its current geometry instead
+    Deny,
+    // grant changes of the size but not of the position
+    AllowSizeOnly,
+    // grant the request, shrunk and moved as needed to stay on the window's output
+    ClampToOutput,
+}
+
+impl ConfigurePolicy {
+    /// Returns what is granted of a request under this policy, given the window's current
+    /// geometry and the area of the output it is on.
+    pub fn grant(self, request: &WinConfigure, current: Area, output: Area) -> WinConfigure {
+        let mut granted = request.clone();
+        match self {
+            ConfigurePolicy::Allow => (),
+            ConfigurePolicy::Deny => {
+                granted.x = None;
+                granted.y = None;
+                granted.width = None;
+                granted.height = None;
+            }
+            ConfigurePolicy::AllowSizeOnly => {
+                granted.x = None;
+                granted.y = None;
+            }
+            ConfigurePolicy::ClampToOutput => {
+                let ((x, y), (w, h)) = current;
+                let ((out_x, out_y), (out_w, out_h)) = output;
+
+                let w = request.width.unwrap_or(w).min(out_w);
+                let h = request.height.unwrap_or(h).min(out_h);
+                let clamp = |pos: i32, start: i32, total: u32, len: u32| {
+                    let end = i64::from(start) + i64::from(total) - i64::from(len);
+                    i64::from(pos).min(end).max(i64::from(start)) as i32
+                };
+                let new_x = clamp(request.x.unwrap_or(x), out_x, out_w, w);
+                let new_y = clamp(request.y.unwrap_or(y), out_y, out_h, h);
+
+                granted.width = request.width.map(|_| w);
+                granted.height = request.height.map(|_| h);
+                // the window is moved back onto the output even if only its size was requested
+                granted.x = (request.x.is_some() || new_x != x).then_some(new_x);
+                granted.y = (request.y.is_some() || new_y != y).then_some(new_y);
+            }
+        }
+        granted
+    }
+}
+
+/// A ConfigureRequest that was not granted as asked because of the configure policy.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ConfigureDenial {
+    pub policy: ConfigurePolicy,
+    pub requested: WinConfigure,
+    pub granted: WinConfigure,
+}
+
+/// A monitor as reported by RandR. Rotation is given in degrees.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OutputInfo {
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub primary: bool,
+    pub rotation: u16,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Event {
+    WindowMapped(WindowInfo),
+    WindowUnmapped(Window),
+    WindowIconified(Window),
+    ConfigureRequested(WinConfigure),
+    ConfigureDenied(ConfigureDenial),
+    FocusChanged(Window),
+    Committed,
+    OutputConnected(OutputInfo),
+    OutputDisconnected(String),
+    OutputChanged(OutputInfo),
+}
+
+/// A request tagged with a client chosen id, which is echoed back in the response.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct RequestEnvelope {
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub id: Option<Value>,
+    pub request: Request,
+}
+
+/// Input accepted on the line protocol: either a bare request or one wrapped in an envelope.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Input {
+    Envelope(RequestEnvelope),
+    Request(Request),
+}
+
+impl From<Input> for RequestEnvelope {
+    fn from(input: Input) -> Self {
+        match input {
+            Input::Envelope(envelope) => envelope,
+            Input::Request(request) => RequestEnvelope { id: None, request },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub enum ErrorType {
+    InvalidInput(String),
+    InternalError(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub enum ResponseBody {
+    Error(ErrorType),
+    Result(Response),
+    Event(Event),
+}
+
+/// Everything written back to a client; `id` is that of the request being answered, if any.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ResponseEnvelope {
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub id: Option<Value>,
+    #[serde(flatten)]
+    pub body: ResponseBody,
 }
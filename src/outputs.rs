@@ -0,0 +1,78 @@
+use x11rb::connection::RequestConnection;
+use x11rb::protocol::randr::{self, ConnectionExt as _, NotifyMask, Rotation};
+use x11rb::protocol::xproto::Window;
+use x11rb::rust_connection::RustConnection;
+
+use crate::error::*;
+use crate::messages::OutputInfo;
+
+/// Returns whether the server supports RandR, and if so, asks it to report changes to the
+/// screen size and outputs on the given root window.
+pub(crate) fn init_randr(conn: &RustConnection, root: Window) -> Result<bool, Error> {
+    if conn
+        .extension_information(randr::X11_EXTENSION_NAME)?
+        .is_none()
+    {
+        return Ok(false);
+    }
+
+    conn.randr_query_version(1, 5)?.reply()?;
+
+    let mask = NotifyMask::SCREEN_CHANGE | NotifyMask::CRTC_CHANGE | NotifyMask::OUTPUT_CHANGE;
+    conn.randr_select_input(root, mask)?.check()?;
+
+    Ok(true)
+}
+
+/// Lists the outputs that are connected and driven by a CRTC, along with their geometry.
+pub(crate) fn query_outputs(conn: &RustConnection, root: Window) -> Result<Vec<OutputInfo>, Error> {
+    let resources = conn.randr_get_screen_resources_current(root)?.reply()?;
+    let primary = conn.randr_get_output_primary(root)?.reply()?.output;
+    let timestamp = resources.config_timestamp;
+
+    let mut cookies = Vec::with_capacity(resources.outputs.len());
+    for &output in &resources.outputs {
+        cookies.push((output, conn.randr_get_output_info(output, timestamp)?));
+    }
+
+    let mut outputs = Vec::new();
+    for (output, cookie) in cookies {
+        let info = cookie.reply()?;
+        if info.connection != randr::Connection::CONNECTED || info.crtc == x11rb::NONE {
+            continue;
+        }
+
+        let crtc = conn.randr_get_crtc_info(info.crtc, timestamp)?.reply()?;
+
+        outputs.push(OutputInfo {
+            name: String::from_utf8_lossy(&info.name).into_owned(),
+            x: i32::from(crtc.x),
+            y: i32::from(crtc.y),
+            width: u32::from(crtc.width),
+            height: u32::from(crtc.height),
+            primary: output == primary,
+            rotation: rotation_degrees(crtc.rotation),
+        });
+    }
+
+    Ok(outputs)
+}
+
+/// Whether the rotation turns the screen sideways, swapping its width and height.
+pub(crate) fn is_sideways(rotation: Rotation) -> bool {
+    let sideways = u16::from(Rotation::ROTATE90) | u16::from(Rotation::ROTATE270);
+    u16::from(rotation) & sideways != 0
+}
+
+fn rotation_degrees(rotation: Rotation) -> u16 {
+    let rotation = u16::from(rotation);
+    if rotation & u16::from(Rotation::ROTATE90) != 0 {
+        90
+    } else if rotation & u16::from(Rotation::ROTATE180) != 0 {
+        180
+    } else if rotation & u16::from(Rotation::ROTATE270) != 0 {
+        270
+    } else {
+        0
+    }
+}
@@ -0,0 +1,110 @@
+use x11rb::properties::WmClass;
+use x11rb::protocol::xproto::*;
+use x11rb::rust_connection::RustConnection;
+
+use crate::atoms::Atoms;
+use crate::error::*;
+use crate::messages::WindowProperties;
+
+/// Reads the metadata of a window. All requests are sent up front so only a single round trip
+/// is needed. Properties that are missing or malformed are left empty.
+pub(crate) fn query_properties(
+    conn: &RustConnection,
+    atoms: &Atoms,
+    win: Window,
+) -> Result<WindowProperties, Error> {
+    let any: Atom = AtomEnum::ANY.into();
+
+    let net_name = conn.get_property(
+        false,
+        win,
+        atoms._NET_WM_NAME,
+        atoms.UTF8_STRING,
+        0,
+        u32::MAX,
+    )?;
+    let name = conn.get_property(false, win, AtomEnum::WM_NAME, any, 0, u32::MAX)?;
+    let class = WmClass::get(conn, win)?;
+    let pid = conn.get_property(false, win, atoms._NET_WM_PID, AtomEnum::CARDINAL, 0, 1)?;
+    let role = conn.get_property(false, win, atoms.WM_WINDOW_ROLE, any, 0, u32::MAX)?;
+    let machine = conn.get_property(false, win, atoms.WM_CLIENT_MACHINE, any, 0, u32::MAX)?;
+    let window_type = conn.get_property(
+        false,
+        win,
+        atoms._NET_WM_WINDOW_TYPE,
+        AtomEnum::ATOM,
+        0,
+        u32::MAX,
+    )?;
+    let window_state =
+        conn.get_property(false, win, atoms._NET_WM_STATE, AtomEnum::ATOM, 0, u32::MAX)?;
+
+    let (class, instance) = match class.reply().ok().flatten() {
+        Some(class) => (
+            Some(String::from_utf8_lossy(class.class()).into_owned()),
+            Some(String::from_utf8_lossy(class.instance()).into_owned()),
+        ),
+        None => (None, None),
+    };
+
+    let window_type = window_type
+        .reply()
+        .ok()
+        .and_then(|reply| {
+            let types = reply
+                .value32()?
+                .filter_map(|atom| atoms.window_type_name(atom))
+                .map(str::to_owned)
+                .collect();
+            Some(types)
+        })
+        .unwrap_or_default();
+
+    let window_state = window_state
+        .reply()
+        .ok()
+        .and_then(|reply| {
+            let states = reply
+                .value32()?
+                .filter_map(|atom| atoms.window_state_name(atom))
+                .map(str::to_owned)
+                .collect();
+            Some(states)
+        })
+        .unwrap_or_default();
+
+    Ok(WindowProperties {
+        title: text(net_name.reply().ok()).or_else(|| text(name.reply().ok())),
+        class,
+        instance,
+        pid: pid.reply().ok().and_then(|reply| reply.value32()?.next()),
+        role: text(role.reply().ok()),
+        client_machine: text(machine.reply().ok()),
+        window_type,
+        window_state,
+    })
+}
+
+/// Returns whether a change of the given property affects the metadata of a window.
+pub(crate) fn is_tracked_property(atoms: &Atoms, atom: Atom) -> bool {
+    let tracked: [Atom; 8] = [
+        atoms._NET_WM_NAME,
+        AtomEnum::WM_NAME.into(),
+        AtomEnum::WM_CLASS.into(),
+        atoms._NET_WM_PID,
+        atoms.WM_WINDOW_ROLE,
+        atoms.WM_CLIENT_MACHINE,
+        atoms._NET_WM_WINDOW_TYPE,
+        atoms._NET_WM_STATE,
+    ];
+    tracked.contains(&atom)
+}
+
+fn text(reply: Option<GetPropertyReply>) -> Option<String> {
+    let reply = reply?;
+    if reply.format != 8 || reply.value.is_empty() {
+        return None;
+    }
+    let value = String::from_utf8_lossy(&reply.value);
+    Some(value.trim_end_matches('\0').to_owned())
+}
@@ -0,0 +1,272 @@
+use std::convert::TryFrom;
+use std::env;
+use std::fs;
+use std::io::ErrorKind as IOErrorKind;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+use crate::error::*;
+use crate::messages::{Length, Rule, RuleActions, WindowProperties};
+
+// bounds the walk up the process tree
+const MAX_ANCESTRY_DEPTH: usize = 64;
+
+struct CompiledRule {
+    rule: Rule,
+    title: Option<Regex>,
+}
+
+/// The rules consulted whenever a window is mapped, in the order they were added.
+#[derive(Default)]
+pub struct RuleSet {
+    rules: Vec<CompiledRule>,
+}
+
+/// Returns the default location of the rules file,
+/// `$XDG_CONFIG_HOME/x11wmgr/rules.json` or `~/.config/x11wmgr/rules.json`.
+pub fn default_rules_path() -> Option<PathBuf> {
+    let dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(dir.join("x11wmgr").join("rules.json"))
+}
+
+impl RuleSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads a JSON list of rules from the given file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        let rules: Vec<Rule> = serde_json::from_str(&text)
+            .map_err(|err| ErrorKind::ConfigError(format!("{}: {}", path.display(), err)))?;
+
+        let mut set = Self::new();
+        for rule in rules {
+            set.add(rule)?;
+        }
+        Ok(set)
+    }
+
+    /// Like `load`, but a missing file yields an empty set of rules.
+    pub fn load_if_exists<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        match fs::metadata(path.as_ref()) {
+            Err(err) if err.kind() == IOErrorKind::NotFound => Ok(Self::new()),
+            _ => Self::load(path),
+        }
+    }
+
+    /// Adds a rule, replacing the one with the same name if there is any.
+    pub fn add(&mut self, rule: Rule) -> Result<(), Error> {
+        if rule.name.is_empty() {
+            let msg = "rule names must not be empty".to_owned();
+            return Err(ErrorKind::InvalidRequest(msg).into());
+        }
+
+        let title = match &rule.criteria.title {
+            Some(pattern) => Some(Regex::new(pattern).map_err(|err| {
+                ErrorKind::InvalidRequest(format!(
+                    "invalid title pattern in {}: {}",
+                    rule.name, err
+                ))
+            })?),
+            None => None,
+        };
+
+        validate_actions(&rule)?;
+
+        let compiled = CompiledRule { rule, title };
+        match self
+            .rules
+            .iter_mut()
+            .find(|r| r.rule.name == compiled.rule.name)
+        {
+            Some(existing) => *existing = compiled,
+            None => self.rules.push(compiled),
+        }
+        Ok(())
+    }
+
+    /// Removes the rule with the given name. Returns `false` if there was no such rule.
+    pub fn remove(&mut self, name: &str) -> bool {
+        let len = self.rules.len();
+        self.rules.retain(|r| r.rule.name != name);
+        self.rules.len() != len
+    }
+
+    pub fn list(&self) -> Vec<Rule> {
+        self.rules.iter().map(|r| r.rule.clone()).collect()
+    }
+
+    /// Combines the actions of every rule matching a window with the given properties,
+    /// or returns `None` if no rule matches.
+    pub fn actions_for(&self, properties: &WindowProperties) -> Option<RuleActions> {
+        // the process tree is only walked if a rule asks for it
+        let mut ancestry = None;
+
+        let mut actions: Option<RuleActions> = None;
+        for rule in &self.rules {
+            if rule.matches(properties, &mut ancestry) {
+                let merged = actions.get_or_insert_with(RuleActions::default);
+                merge(merged, &rule.rule.actions);
+            }
+        }
+        actions
+    }
+}
+
+// reject actions that could never be applied, as far as that is known before a window is mapped
+fn validate_actions(rule: &Rule) -> Result<(), Error> {
+    let actions = &rule.actions;
+    let invalid = |what: &str| {
+        let msg = format!("invalid {} in {}", what, rule.name);
+        Err(ErrorKind::InvalidRequest(msg).into())
+    };
+
+    if actions.workspace.as_deref() == Some("") {
+        return invalid("workspace name");
+    }
+    for (what, pos) in [("x", &actions.x), ("y", &actions.y)] {
+        if matches!(pos, Some(pos) if !is_valid_position(*pos)) {
+            return invalid(what);
+        }
+    }
+    for (what, size) in [("width", &actions.width), ("height", &actions.height)] {
+        if matches!(size, Some(size) if !is_valid_size(*size)) {
+            return invalid(what);
+        }
+    }
+    Ok(())
+}
+
+// positions are offsets that X11 takes as 16-bit signed integers
+fn is_valid_position(pos: Length) -> bool {
+    match pos {
+        Length::Pixels(px) => i16::try_from(px).is_ok(),
+        Length::Percent(percent) => percent.is_finite(),
+    }
+}
+
+// sizes are non-zero 16-bit unsigned integers to X11
+fn is_valid_size(size: Length) -> bool {
+    match size {
+        Length::Pixels(px) => matches!(u16::try_from(px), Ok(px) if px > 0),
+        Length::Percent(percent) => percent.is_finite() && percent > 0.0,
+    }
+}
+
+impl CompiledRule {
+    fn matches(
+        &self,
+        properties: &WindowProperties,
+        ancestry: &mut Option<Vec<(u32, String)>>,
+    ) -> bool {
+        let criteria = &self.rule.criteria;
+
+        let equals = |wanted: &Option<String>, actual: &Option<String>| match wanted {
+            Some(wanted) => actual.as_ref() == Some(wanted),
+            None => true,
+        };
+
+        if !equals(&criteria.class, &properties.class)
+            || !equals(&criteria.instance, &properties.instance)
+            || !equals(&criteria.role, &properties.role)
+        {
+            return false;
+        }
+
+        if let Some(title) = &self.title {
+            match &properties.title {
+                Some(actual) if title.is_match(actual) => (),
+                _ => return false,
+            }
+        }
+
+        if let Some(window_type) = &criteria.window_type {
+            if !properties.window_type.contains(window_type) {
+                return false;
+            }
+        }
+
+        if criteria.ancestor_pid.is_none() && criteria.ancestor.is_none() {
+            return true;
+        }
+
+        let pid = match properties.pid {
+            Some(pid) => pid,
+            None => return false,
+        };
+        let ancestry = ancestry.get_or_insert_with(|| process_ancestry(pid));
+
+        if let Some(ancestor_pid) = criteria.ancestor_pid {
+            if !ancestry.iter().any(|(pid, _)| *pid == ancestor_pid) {
+                return false;
+            }
+        }
+        if let Some(ancestor) = &criteria.ancestor {
+            if !ancestry.iter().any(|(_, name)| name == ancestor) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+fn merge(actions: &mut RuleActions, other: &RuleActions) {
+    let RuleActions {
+        visible,
+        zindex,
+        workspace,
+        output,
+        x,
+        y,
+        width,
+        height,
+        focus,
+    } = other.clone();
+
+    actions.visible = visible.or(actions.visible);
+    actions.zindex = zindex.or(actions.zindex);
+    actions.workspace = workspace.or_else(|| actions.workspace.take());
+    actions.output = output.or_else(|| actions.output.take());
+    actions.x = x.or(actions.x);
+    actions.y = y.or(actions.y);
+    actions.width = width.or(actions.width);
+    actions.height = height.or(actions.height);
+    actions.focus = focus.or(actions.focus);
+}
+
+// the process itself followed by its parents, as (pid, command name), read from /proc
+fn process_ancestry(pid: u32) -> Vec<(u32, String)> {
+    let mut ancestry = Vec::new();
+    let mut pid = pid;
+
+    while pid != 0 && ancestry.len() < MAX_ANCESTRY_DEPTH {
+        let stat = match fs::read_to_string(format!("/proc/{}/stat", pid)) {
+            Ok(stat) => stat,
+            Err(_) => break,
+        };
+
+        // the command name is in parentheses and may itself contain spaces or parentheses
+        let (name, rest) = match (stat.find('('), stat.rfind(')')) {
+            (Some(start), Some(end)) if start < end => (&stat[start + 1..end], &stat[end + 1..]),
+            _ => break,
+        };
+        ancestry.push((pid, name.to_owned()));
+
+        // the state comes first, followed by the pid of the parent
+        pid = match rest
+            .split_whitespace()
+            .nth(1)
+            .and_then(|ppid| ppid.parse().ok())
+        {
+            Some(ppid) => ppid,
+            None => break,
+        };
+    }
+
+    ancestry
+}
@@ -0,0 +1,138 @@
+use std::env;
+use std::fs;
+use std::io::ErrorKind as IOErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::error::*;
+use crate::messages::{Scene, SceneWindow, WindowProperties};
+use crate::windowmanager::Window;
+
+/// Scenes by name, written back to a JSON file whenever they change.
+#[derive(Default)]
+pub struct SceneStore {
+    path: Option<PathBuf>,
+    scenes: Vec<Scene>,
+}
+
+/// Returns the default location of the scenes file,
+/// `$XDG_DATA_HOME/x11wmgr/scenes.json` or `~/.local/share/x11wmgr/scenes.json`.
+pub fn default_scenes_path() -> Option<PathBuf> {
+    let dir = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))?;
+    Some(dir.join("x11wmgr").join("scenes.json"))
+}
+
+impl SceneStore {
+    /// Creates a store that is only kept in memory.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Opens the store persisted at the given path. A missing file yields an empty store,
+    /// which is created once the first scene is saved.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let scenes = match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map_err(|err| ErrorKind::ConfigError(format!("{}: {}", path.display(), err)))?,
+            Err(err) if err.kind() == IOErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(SceneStore {
+            path: Some(path.to_owned()),
+            scenes,
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Scene> {
+        self.scenes.iter().find(|scene| scene.name == name)
+    }
+
+    pub fn list(&self) -> Vec<Scene> {
+        self.scenes.clone()
+    }
+
+    /// Stores a scene, replacing the one with the same name if there is any.
+    pub fn save(&mut self, scene: Scene) -> Result<(), Error> {
+        match self.scenes.iter_mut().find(|s| s.name == scene.name) {
+            Some(existing) => *existing = scene,
+            None => self.scenes.push(scene),
+        }
+        self.persist()
+    }
+
+    /// Deletes the scene with the given name. Returns `false` if there was no such scene.
+    pub fn delete(&mut self, name: &str) -> Result<bool, Error> {
+        let len = self.scenes.len();
+        self.scenes.retain(|scene| scene.name != name);
+        if self.scenes.len() == len {
+            return Ok(false);
+        }
+        self.persist()?;
+        Ok(true)
+    }
+
+    fn persist(&self) -> Result<(), Error> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        // write to the side first so a crash never leaves a truncated file behind
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(&self.scenes).unwrap())?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+/// Pairs the windows of a scene with the given candidates. A window whose class, instance and
+/// title all match is preferred; failing that, one with the same class and instance is taken.
+/// Every candidate is used at most once.
+pub fn match_windows<'a>(
+    scene: &'a Scene,
+    candidates: &[(Window, WindowProperties)],
+) -> Vec<(Window, &'a SceneWindow)> {
+    let mut taken = vec![false; candidates.len()];
+    let mut matched = vec![None; scene.windows.len()];
+
+    let same_class = |entry: &SceneWindow, props: &WindowProperties| {
+        entry.class == props.class && entry.instance == props.instance
+    };
+
+    for (i, entry) in scene.windows.iter().enumerate() {
+        let found = (0..candidates.len()).find(|&j| {
+            let props = &candidates[j].1;
+            !taken[j] && same_class(entry, props) && entry.title == props.title
+        });
+        if let Some(j) = found {
+            taken[j] = true;
+            matched[i] = Some(j);
+        }
+    }
+
+    for (i, entry) in scene.windows.iter().enumerate() {
+        if matched[i].is_some() {
+            continue;
+        }
+        let found =
+            (0..candidates.len()).find(|&j| !taken[j] && same_class(entry, &candidates[j].1));
+        if let Some(j) = found {
+            taken[j] = true;
+            matched[i] = Some(j);
+        }
+    }
+
+    scene
+        .windows
+        .iter()
+        .zip(matched)
+        .filter_map(|(entry, j)| j.map(|j| (candidates[j].0, entry)))
+        .collect()
+}
@@ -0,0 +1,104 @@
+use std::env;
+use std::fs;
+use std::io::{BufReader, ErrorKind as IOErrorKind};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::cli::{serve_client, Output, PendingRequest};
+use crate::error::*;
+use crate::messages::Event;
+use crate::windowmanager::Waker;
+
+/// Returns the default location of the control socket,
+/// `$XDG_RUNTIME_DIR/x11wmgr-$DISPLAY.sock`.
+pub fn default_socket_path() -> PathBuf {
+    let dir = env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(env::temp_dir);
+    let display = env::var("DISPLAY").unwrap_or_default();
+    dir.join(format!("x11wmgr-{}.sock", display))
+}
+
+/// Removes the control socket once dropped, so it is not left behind when the window manager
+/// exits.
+pub struct SocketListener {
+    path: PathBuf,
+}
+
+impl Drop for SocketListener {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Listens for clients on a Unix domain socket at the given path. Every client speaks the same
+/// newline delimited JSON protocol as stdin, and receives its own responses and events.
+/// Fails if another window manager is already listening on it. The socket is removed once the
+/// returned `SocketListener` is dropped.
+pub fn create_socket_listener<P: AsRef<Path>>(
+    path: P,
+    waker: Waker,
+    tx_req: Sender<PendingRequest>,
+    events: Receiver<Event>,
+) -> Result<SocketListener, Error> {
+    let path = path.as_ref();
+
+    if let Ok(meta) = fs::symlink_metadata(path) {
+        if meta.file_type().is_socket() {
+            match UnixStream::connect(path) {
+                Ok(_) => {
+                    let msg = format!("another instance is listening on {}", path.display());
+                    return Err(ErrorKind::ConfigError(msg).into());
+                }
+                // a socket left behind by a previous run would make bind fail
+                Err(err) if err.kind() == IOErrorKind::ConnectionRefused => {
+                    fs::remove_file(path)?;
+                }
+                Err(_) => {}
+            }
+        }
+    }
+
+    let listener = UnixListener::bind(path)?;
+    let guard = SocketListener {
+        path: path.to_owned(),
+    };
+
+    let clients: Arc<Mutex<Vec<Sender<Event>>>> = Arc::new(Mutex::new(Vec::new()));
+
+    // fan events out to every connected client
+    {
+        let clients = clients.clone();
+        thread::spawn(move || {
+            for event in events {
+                let mut clients = clients.lock().unwrap();
+                clients.retain(|tx| tx.send(event.clone()).is_ok());
+            }
+        });
+    }
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let reader = match stream.try_clone() {
+                Ok(reader) => BufReader::new(reader),
+                Err(_) => continue,
+            };
+
+            let (tx_events, rx_events) = channel();
+            clients.lock().unwrap().push(tx_events);
+
+            let waker = waker.clone();
+            let tx_req = tx_req.clone();
+            thread::spawn(move || {
+                let output: Output = Arc::new(Mutex::new(stream));
+                serve_client(reader, output, waker, tx_req, rx_events);
+            });
+        }
+    });
+
+    Ok(guard)
+}
@@ -0,0 +1,69 @@
+use std::cmp;
+use std::time::Instant;
+
+use crate::messages::{StackLayer, WindowProperties};
+use crate::windowmanager::{Window, ZIndexType};
+
+/// What decides where a window goes when the windows are restacked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackEntry {
+    pub id: Window,
+    // visible on the current workspace
+    pub shown: bool,
+    pub layer: StackLayer,
+    pub zindex: ZIndexType,
+    // when the z-index or visibility of the window was last changed
+    pub updated: Instant,
+    pub discovered: Instant,
+}
+
+impl StackEntry {
+    /// Orders windows within a stack from bottom to top: by z-index, with the most recently
+    /// updated window on top of those sharing a z-index, then the one discovered last.
+    pub fn key(&self) -> (ZIndexType, Instant, Instant, Window) {
+        (self.zindex, self.updated, self.discovered, self.id)
+    }
+}
+
+/// Returns the order in which a commit restacks the windows: the shown ones from bottom to top by
+/// layer and z-index, and all others from top to bottom, as they are pushed to the bottom one
+/// after another.
+pub fn commit_order(entries: &[StackEntry]) -> (Vec<Window>, Vec<Window>) {
+    let mut shown = entries
+        .iter()
+        .filter(|entry| entry.shown)
+        .collect::<Vec<_>>();
+    shown.sort_by_key(|entry| (entry.layer, entry.key()));
+
+    let mut hidden = entries
+        .iter()
+        .filter(|entry| !entry.shown)
+        .collect::<Vec<_>>();
+    hidden.sort_by_key(|entry| cmp::Reverse(entry.key()));
+
+    (
+        shown.into_iter().map(|entry| entry.id).collect(),
+        hidden.into_iter().map(|entry| entry.id).collect(),
+    )
+}
+
+/// Returns the stacking order from bottom to top that restacking in commit order results in.
+pub fn stacking_order(shown: &[Window], hidden: &[Window]) -> Vec<Window> {
+    // every hidden window went to the very bottom, so the last one ends up lowest
+    hidden.iter().rev().chain(shown).cloned().collect()
+}
+
+/// Returns the explicitly set layer, or the one following from the window's state and type.
+pub fn implied_layer(layer: Option<StackLayer>, properties: &WindowProperties) -> StackLayer {
+    let has_state = |state: &str| properties.window_state.iter().any(|s| s == state);
+    let has_type = |kind: &str| properties.window_type.iter().any(|t| t == kind);
+
+    match layer {
+        Some(layer) => layer,
+        None if has_state("ABOVE") => StackLayer::Above,
+        None if has_state("BELOW") => StackLayer::Below,
+        None if has_type("NOTIFICATION") => StackLayer::Overlay,
+        None if has_type("DOCK") => StackLayer::Above,
+        None => StackLayer::Normal,
+    }
+}
@@ -1,24 +1,56 @@
 use std::cmp;
-use std::collections::HashMap;
-use std::time::Instant;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::io::ErrorKind as IOErrorKind;
+use std::os::unix::io::AsRawFd;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use std::sync::Arc;
 
+use mio::unix::SourceFd;
+use mio::{Events, Interest, Poll, Token};
 use x11rb::connection::Connection;
+use x11rb::protocol::randr::ScreenChangeNotifyEvent;
 use x11rb::protocol::xproto::*;
-use x11rb::protocol::Event;
+use x11rb::protocol::Event as XEvent;
 use x11rb::rust_connection::RustConnection;
-use x11rb::x11_utils::TryParse;
 use x11rb::COPY_DEPTH_FROM_PARENT;
 
 pub use x11rb::protocol::xproto::Window;
 
 pub type ZIndexType = u32;
 
-use crate::error::*;
-use crate::messages::{WinMove, WinResize, WinVisbilty, WinZIndex, WindowInfo};
+// position and size a window is to be given, either of which may be left as it is
+type PendingGeometry = (Option<(i32, i32)>, Option<(u32, u32)>);
 
-const PENDING_INPUT_ATOM_NAME: &'static str = "__WMGR_PENDING_INPUT";
+use crate::atoms::Atoms;
+use crate::error::*;
+use crate::ewmh::{self, ICONIC_STATE, NORMAL_STATE};
+use crate::hints::{query_size_hints, SizeHints};
+use crate::history::History;
+use crate::layout::{arrange, Area};
+use crate::messages::{
+    Change, CommitReport, ConfigureDenial, ConfigureOp, ConfigurePolicy, Event, HistoryEntry,
+    Layout, LayoutSpec, Length, OutputInfo, PendingChanges, Rule, RuleActions, Scene, SceneWindow,
+    StackLayer, StackPosition, WinChanges, WinClosed, WinConfigure, WinFailure, WinGeometry,
+    WinLayer, WinLayout, WinMove, WinResize, WinSnapshot, WinVisbilty, WinWorkspace, WinZIndex,
+    WindowInfo, WindowProperties, WorkspaceInfo,
+};
+use crate::outputs::{init_randr, is_sideways, query_outputs};
+use crate::properties::{is_tracked_property, query_properties};
+use crate::rules::RuleSet;
+use crate::scenes::{match_windows, SceneStore};
+use crate::stacking::{self, implied_layer, StackEntry};
+
+// sources the event loop waits on
+const X11_TOKEN: Token = Token(0);
+const WAKER_TOKEN: Token = Token(1);
+
+const DEFAULT_WORKSPACE_NAME: &str = "1";
+
+// number of committed states kept for undo and redo
+const HISTORY_CAPACITY: usize = 32;
 
 #[derive(Clone, Debug)]
 struct WinInfo {
@@ -28,14 +60,50 @@ struct WinInfo {
     // the time the window was mapped/discovered
     discovery_time: Instant,
 
-    // last time window zindex or visibilty was updated
+    // last time window zindex or visibilty was updated, breaks ties in z-index
     last_update_time: Instant,
+
+    // metadata read from the window's properties
+    properties: WindowProperties,
+
+    // constraints on the window's size from WM_NORMAL_HINTS
+    hints: SizeHints,
+
+    // position and size of the window as last known to the server
+    loc: (i32, i32),
+    size: (u32, u32),
+
+    // window was explicitly moved or resized, so a layout may leave it floating
+    floating: bool,
+
+    // index of the workspace the window belongs to
+    workspace: usize,
+
+    // layer the window was explicitly put in, otherwise it follows from its type and state
+    layer: Option<StackLayer>,
+
+    // how the window's ConfigureRequests are dealt with, unless the global policy applies
+    configure_policy: Option<ConfigurePolicy>,
+
+    // window was unmapped on the client's request to iconify it, and is shown again once remapped
+    iconic: bool,
+
+    // visibility, z-index, workspace, layer and floating as of the last commit
+    committed: WinState,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct WinState {
+    visible: bool,
+    index: ZIndexType,
+    workspace: usize,
+    layer: Option<StackLayer>,
+    floating: bool,
 }
 
+#[derive(Clone)]
 pub struct Waker {
-    conn: Arc<RustConnection>,
-    win: Window,
-    event: ClientMessageEvent,
+    waker: Arc<mio::Waker>,
 }
 
 pub struct WindowManager {
@@ -45,34 +113,87 @@ pub struct WindowManager {
     // window that spans the entire screen and has a black background.
     virtual_root_win: Window,
 
-    // windows that are currently in the visible stack
+    // windows that are currently in the visible stack of their workspace
     visible_wins: HashMap<Window, WinInfo>,
 
-    // windows that are currently in the hidden stack
+    // windows that are currently in the hidden stack of their workspace
     hidden_wins: HashMap<Window, WinInfo>,
 
+    // withdrawn windows as they were when unmapped, along with whether they were in the visible
+    // stack, so they can return to the same place once remapped
+    withdrawn_wins: HashMap<Window, (bool, WinInfo)>,
+
+    // whether withdrawn windows are remembered until they are destroyed
+    remember_withdrawn: bool,
+
+    // names of the workspaces, in the order they were created
+    workspaces: Vec<String>,
+
+    // index of the workspace whose visible windows are shown on commit
+    current_workspace: usize,
+
+    // index of the workspace that was current as of the last commit
+    committed_workspace: usize,
+
     // Tracks the pending move operations for windows, storing their new (x, y) coordinates.
     windows_loc: HashMap<Window, (i32, i32)>,
 
     // Tracks the pending resize operations for windows, storing their new (width, height) dimensions.
     windows_size: HashMap<Window, (u32, u32)>,
 
+    // windows whose pending size is applied as is, regardless of their size hints
+    unhinted_sizes: HashSet<Window>,
+
+    // outputs (monitors) as last reported by RandR
+    outputs: Vec<OutputInfo>,
+
+    // current size of the screen, which may change through RandR
+    screen_size: (u32, u32),
+
+    // layout that visible windows are tiled with on commit, if any
+    layout: Option<LayoutSpec>,
+
+    // how ConfigureRequests of windows without a policy of their own are dealt with
+    configure_policy: ConfigurePolicy,
+
+    // rules applied to windows as they are mapped
+    rules: RuleSet,
+
+    // saved arrangements of windows
+    scenes: SceneStore,
+
+    // states as of the last few commits, for undo and redo
+    history: History<HistoryEntry>,
+
+    // managed windows from bottom to top, as last stacked
+    stacking: Vec<Window>,
+
+    // window last focused, as published through _NET_ACTIVE_WINDOW
+    active_win: Option<Window>,
+
     // the last time new windows were queried
     last_discovery_time: Instant,
 
-    // pending input atom
-    pending_input_atom: Atom,
+    // the last time handed out for a window update, see `next_update_time`
+    last_update_time: Instant,
+
+    // waits on the X11 connection and on wake ups signalling pending input
+    poll: Poll,
+    waker: Arc<mio::Waker>,
+
+    // atoms of the properties read from client windows
+    atoms: Atoms,
+
+    // channels of everyone interested in window lifecycle events
+    subscribers: Vec<Sender<Event>>,
+    #[cfg(feature = "async")]
+    async_subscribers: Vec<tokio::sync::mpsc::UnboundedSender<Event>>,
 }
 
 impl Waker {
     // wake up wm thread, notifying it of pending input
     pub fn wake(&self) -> Result<(), Error> {
-        let cookie =
-            self.conn
-                .send_event(false, self.win, EventMask::SUBSTRUCTURE_NOTIFY, &self.event)?;
-
-        cookie.check()?;
-
+        self.waker.wake()?;
         Ok(())
     }
 }
@@ -84,9 +205,13 @@ impl WindowManager {
     pub fn new() -> Result<Self, Error> {
         let (conn, screen_num) = RustConnection::connect(None)?;
 
-        let pending_input_atom = intern_atom(&conn, false, PENDING_INPUT_ATOM_NAME.as_bytes())?
-            .reply()?
-            .atom;
+        let poll = Poll::new()?;
+        let fd = conn.stream().as_raw_fd();
+        poll.registry()
+            .register(&mut SourceFd(&fd), X11_TOKEN, Interest::READABLE)?;
+        let waker = Arc::new(mio::Waker::new(poll.registry(), WAKER_TOKEN)?);
+
+        let atoms = Atoms::new(&conn)?.reply()?;
 
         let screen = &conn.setup().roots[screen_num];
 
@@ -106,6 +231,11 @@ impl WindowManager {
             &CreateWindowAux::new().background_pixel(screen.black_pixel),
         )?;
 
+        let screen_size = (
+            u32::from(screen.width_in_pixels),
+            u32::from(screen.height_in_pixels),
+        );
+
         conn.map_window(wid)?;
         conn.flush()?;
 
@@ -115,55 +245,93 @@ impl WindowManager {
             virtual_root_win: wid,
             visible_wins: HashMap::new(),
             hidden_wins: HashMap::new(),
+            withdrawn_wins: HashMap::new(),
+            remember_withdrawn: true,
+            workspaces: vec![DEFAULT_WORKSPACE_NAME.to_owned()],
+            current_workspace: 0,
+            committed_workspace: 0,
             windows_loc: HashMap::new(),
             windows_size: HashMap::new(),
+            unhinted_sizes: HashSet::new(),
+            outputs: Vec::new(),
+            screen_size,
+            layout: None,
+            configure_policy: ConfigurePolicy::default(),
+            rules: RuleSet::new(),
+            scenes: SceneStore::new(),
+            history: History::new(HISTORY_CAPACITY),
+            stacking: Vec::new(),
+            active_win: None,
             last_discovery_time: Instant::now(),
-            pending_input_atom,
+            last_update_time: Instant::now(),
+            poll,
+            waker,
+            atoms,
+            subscribers: Vec::new(),
+            #[cfg(feature = "async")]
+            async_subscribers: Vec::new(),
         };
 
         wm.become_wm()?;
+        wm.init_outputs()?;
         wm.scan_windows()?;
+        wm.update_client_lists()?;
+        wm.update_desktops()?;
+        wm.record_history();
 
         Ok(wm)
     }
 
     /// Processes incoming X11 events in a blocking manner.
     /// This method will handle events such as window mapping, unmapping, and configuration requests.
+    /// Returns once a `Waker` signals pending input, after handling all events received so far.
     pub fn process_events(&mut self) -> Result<(), Error> {
-        while let Ok(event) = self.conn.wait_for_event() {
-            if !self.handle_event(event)? {
-                break;
+        let mut events = Events::with_capacity(2);
+        let mut woken = false;
+
+        loop {
+            // events may already have been read off the connection, e.g. while waiting for a
+            // reply, in which case the connection will not become readable for them
+            while let Some(event) = self.conn.poll_for_event()? {
+                self.handle_event(event)?;
+            }
+            self.conn.flush()?;
+
+            if woken {
+                return Ok(());
+            }
+
+            match self.poll.poll(&mut events, None) {
+                Ok(()) => (),
+                Err(err) if err.kind() == IOErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
             }
+            woken = events.iter().any(|event| event.token() == WAKER_TOKEN);
         }
-        Ok(())
     }
 
     /// Creates a Waker object that can be used to notify the WindowManager of pending input.
     /// This is useful for waking up the event loop when new requests are available.
-    pub fn create_waker(&self) -> Result<Waker, Error> {
-        let atom = self.pending_input_atom;
-
-        let mut data = [0; 20];
-        data[..4].copy_from_slice(&atom.to_ne_bytes());
-
-        let (data, _): (ClientMessageData, _) = TryParse::try_parse(&data)?;
-
-        let win = self.screen_ref().root;
+    pub fn create_waker(&self) -> Waker {
+        Waker {
+            waker: self.waker.clone(),
+        }
+    }
 
-        let event = ClientMessageEvent {
-            response_type: CLIENT_MESSAGE_EVENT,
-            format: 32,
-            sequence: 0,
-            window: win,
-            type_: atom,
-            data,
-        };
+    /// Subscribes to window lifecycle events (mapping, unmapping, configure requests, focus
+    /// changes and commits). Events are delivered until the returned receiver is dropped.
+    pub fn subscribe(&mut self) -> Receiver<Event> {
+        let (tx, rx) = channel();
+        self.subscribers.push(tx);
+        rx
+    }
 
-        Ok(Waker {
-            conn: self.conn.clone(),
-            win,
-            event,
-        })
+    /// Like `subscribe`, but hands out a receiver that can be awaited.
+    #[cfg(feature = "async")]
+    pub fn subscribe_async(&mut self) -> tokio::sync::mpsc::UnboundedReceiver<Event> {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        self.async_subscribers.push(tx);
+        rx
     }
 
     /// Updates the z-index of specified windows.
@@ -177,16 +345,17 @@ impl WindowManager {
 
         for item in iter {
             let WinZIndex { id, zindex } = item.into();
+            let now = self.next_update_time();
             if let Some(v) = self.hidden_wins.get_mut(&id) {
                 if v.index != zindex {
                     v.index = zindex;
-                    v.last_update_time = Instant::now();
+                    v.last_update_time = now;
                     changed_wins.push(id);
                 }
             } else if let Some(v) = self.visible_wins.get_mut(&id) {
                 if v.index != zindex {
                     v.index = zindex;
-                    v.last_update_time = Instant::now();
+                    v.last_update_time = now;
                     changed_wins.push(id);
                 }
             }
@@ -210,15 +379,16 @@ impl WindowManager {
                 id: winid,
                 visible: to_visible,
             } = item.into();
+            let now = self.next_update_time();
             if to_visible {
                 if let Some(mut v) = self.hidden_wins.remove(&winid) {
-                    v.last_update_time = Instant::now();
+                    v.last_update_time = now;
                     self.visible_wins.insert(winid, v);
                     changed_wins.push(winid);
                 }
             } else {
                 if let Some(mut v) = self.visible_wins.remove(&winid) {
-                    v.last_update_time = Instant::now();
+                    v.last_update_time = now;
                     self.hidden_wins.insert(winid, v);
                     changed_wins.push(winid);
                 }
@@ -228,33 +398,250 @@ impl WindowManager {
         changed_wins
     }
 
+    /// Moves the window to the top of its stack, i.e. above the other windows in the same list
+    /// (visible or hidden) of the same workspace, by giving it the highest z-index among them.
+    /// The change will only take effect after the `commit` method is called.
+    /// Returns `false` if the window is not managed.
+    pub fn raise_window(&mut self, id: Window) -> bool {
+        let peers = match self.stack_peers(id) {
+            Some(peers) => peers,
+            None => return false,
+        };
+        if let Some(&top) = peers.last() {
+            if self.zindex_of(id) <= self.zindex_of(top) {
+                self.place_above(id, Some(top), &peers);
+            }
+        }
+        true
+    }
+
+    /// Moves the window to the bottom of its stack, by giving it the lowest z-index among the
+    /// other windows in the same list of the same workspace.
+    /// The change will only take effect after the `commit` method is called.
+    /// Returns `false` if the window is not managed.
+    pub fn lower_window(&mut self, id: Window) -> bool {
+        let peers = match self.stack_peers(id) {
+            Some(peers) => peers,
+            None => return false,
+        };
+        if let Some(&bottom) = peers.first() {
+            if self.zindex_of(id) >= self.zindex_of(bottom) {
+                self.place_above(id, None, &peers);
+            }
+        }
+        true
+    }
+
+    /// Moves the window directly above the sibling, which has to be in the same list of the same
+    /// workspace. The window takes on the z-index of the sibling.
+    /// The change will only take effect after the `commit` method is called.
+    /// Returns `false` if either window is not managed or they are not in the same stack.
+    pub fn stack_above(&mut self, id: Window, sibling: Window) -> bool {
+        match self.stack_peers(id) {
+            Some(peers) if peers.contains(&sibling) => {
+                self.place_above(id, Some(sibling), &peers);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Moves the window directly below the sibling, which has to be in the same list of the same
+    /// workspace. The window takes on the z-index of the window below the sibling, if any.
+    /// The change will only take effect after the `commit` method is called.
+    /// Returns `false` if either window is not managed or they are not in the same stack.
+    pub fn stack_below(&mut self, id: Window, sibling: Window) -> bool {
+        let peers = match self.stack_peers(id) {
+            Some(peers) => peers,
+            None => return false,
+        };
+        match peers.iter().position(|&peer| peer == sibling) {
+            Some(0) => {
+                self.place_above(id, None, &peers);
+                true
+            }
+            Some(pos) => {
+                self.place_above(id, Some(peers[pos - 1]), &peers);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Puts the specified windows in the given layers, or back into the layer implied by their
+    /// window type and state. Visible windows are stacked by layer first and z-index second.
+    /// The changes will only take effect after the `commit` method is called.
+    /// Returns a list of windows whose layer was changed.
+    pub fn set_layer<I, T>(&mut self, iter: I) -> Vec<Window>
+    where
+        I: Iterator<Item = T>,
+        T: Into<WinLayer>,
+    {
+        let mut changed_wins = Vec::new();
+
+        for item in iter {
+            let WinLayer { id, layer } = item.into();
+            let now = self.next_update_time();
+            if let Some(winfo) = self.find_win_mut(id) {
+                if winfo.layer != layer {
+                    winfo.layer = layer;
+                    winfo.last_update_time = now;
+                    changed_wins.push(id);
+                }
+            }
+        }
+
+        changed_wins
+    }
+
+    /// Lets the specified windows be tiled again after they were moved or resized, which leaves
+    /// them floating when the layout is set to float manually placed windows.
+    /// The changes will only take effect after the `commit` method is called.
+    /// Returns a list of windows that were floating.
+    pub fn tile_windows<I>(&mut self, iter: I) -> Vec<Window>
+    where
+        I: Iterator<Item = Window>,
+    {
+        let mut tiled_wins = Vec::new();
+
+        for id in iter {
+            if let Some(winfo) = self.find_win_mut(id) {
+                if winfo.floating {
+                    winfo.floating = false;
+                    tiled_wins.push(id);
+                }
+            }
+        }
+
+        tiled_wins
+    }
+
+    /// Returns the outputs (monitors) that are connected and enabled, as reported by RandR.
+    /// The list is empty if the server does not support RandR.
+    pub fn list_outputs(&self) -> Vec<OutputInfo> {
+        self.outputs.clone()
+    }
+
     /// Sets the input focus to the specified window.
-    /// Returns `true` if the window is in the visible list and the focus was successfully set.
+    /// Returns `true` if the window is in the visible list of the current workspace and the focus
+    /// was successfully set.
     pub fn focus_window(&mut self, id: Window) -> Result<bool, Error> {
-        if self.visible_wins.contains_key(&id) {
+        if self.is_shown(id) {
             let cookie = self
                 .conn
                 .set_input_focus(InputFocus::PARENT, id, Time::CURRENT_TIME)?;
 
             cookie.check()?;
 
+            ewmh::set_active_window(&self.conn, &self.atoms, self.screen_ref().root, id)?;
+            self.active_win = Some(id);
+            self.conn.flush()?;
+
+            self.publish(Event::FocusChanged(id));
+
             Ok(true)
         } else {
             Ok(false)
         }
     }
 
+    /// Asks the specified windows to close by sending them WM_DELETE_WINDOW. Clients that do not
+    /// support the protocol are killed instead. Windows that are not managed are ignored.
+    pub fn close_windows<I>(&mut self, iter: I) -> Result<WinClosed, Error>
+    where
+        I: Iterator<Item = Window>,
+    {
+        let mut closed = WinClosed::default();
+        let ids = iter
+            .filter(|id| self.find_win(*id).is_some())
+            .collect::<Vec<_>>();
+
+        for id in ids {
+            // a window vanishing before it is closed must not fail the others; should it vanish
+            // right after, the resulting error event is recovered from like any other
+            let supported = match self.supports_delete_window(id) {
+                Ok(supported) => supported,
+                Err(err) => {
+                    if err.is_fatal() {
+                        return Err(err);
+                    }
+                    if err.vanished_window().is_some() {
+                        self.drop_window(id)?;
+                    }
+                    closed.failures.push(WinFailure {
+                        id,
+                        error: err.to_string(),
+                    });
+                    continue;
+                }
+            };
+
+            if supported {
+                let data = [self.atoms.WM_DELETE_WINDOW, x11rb::CURRENT_TIME, 0, 0, 0];
+                let event = ClientMessageEvent::new(32, id, self.atoms.WM_PROTOCOLS, data);
+                self.conn
+                    .send_event(false, id, EventMask::NO_EVENT, event)?;
+                closed.asked.push(id);
+            } else {
+                self.conn.kill_client(id)?;
+                closed.killed.push(id);
+            }
+        }
+
+        self.conn.flush()?;
+
+        Ok(closed)
+    }
+
+    /// Forcefully terminates the connection of the clients owning the specified windows.
+    /// Windows that are not managed are ignored.
+    pub fn kill_clients<I>(&mut self, iter: I) -> Result<WinClosed, Error>
+    where
+        I: Iterator<Item = Window>,
+    {
+        let mut closed = WinClosed::default();
+
+        for id in iter.filter(|id| self.find_win(*id).is_some()) {
+            self.conn.kill_client(id)?;
+            closed.killed.push(id);
+        }
+
+        self.conn.flush()?;
+
+        Ok(closed)
+    }
+
     // resize multiple windows (deferred)
-    /// Queues resize operations for the specified windows.
+    /// Queues resize operations for the specified windows. Unless asked to ignore them, sizes are
+    /// adjusted to the size hints of each window when committing.
     /// The changes will only take effect after the `commit` method is called.
+    /// The geometry actually applied is reported by the commit.
     pub fn resize_windows<I, T>(&mut self, iter: I) -> Result<(), Error>
     where
         I: Iterator<Item = T>,
         T: Into<WinResize>,
     {
+        // resolve everything up front so nothing is queued if any entry is invalid
+        let mut sizes = Vec::new();
         for item in iter {
-            let WinResize { id, width, height } = item.into();
-            self.windows_size.insert(id, (width, height));
+            let WinResize {
+                id,
+                output,
+                width,
+                height,
+                ignore_hints,
+            } = item.into();
+            let (_, _, area_w, area_h) = self.reference_area(output.as_deref())?;
+            let width = resolve_size(id, &width, area_w)?;
+            let height = resolve_size(id, &height, area_h)?;
+            sizes.push((id, (width, height), ignore_hints));
+        }
+
+        for (id, size, ignore_hints) in sizes {
+            if let Some(winfo) = self.find_win_mut(id) {
+                winfo.floating = true;
+            }
+            self.stage_size(id, size, ignore_hints);
         }
         Ok(())
     }
@@ -267,263 +654,1831 @@ impl WindowManager {
         I: Iterator<Item = T>,
         T: Into<WinMove>,
     {
+        // resolve everything up front so nothing is queued if any entry is invalid
+        let mut locs = Vec::new();
         for item in iter {
-            let WinMove { id, x, y } = item.into();
-            self.windows_loc.insert(id, (x, y));
+            let WinMove { id, output, x, y } = item.into();
+            let (area_x, area_y, area_w, area_h) = self.reference_area(output.as_deref())?;
+            let x = resolve_position(id, area_x, &x, area_w)?;
+            let y = resolve_position(id, area_y, &y, area_h)?;
+            locs.push((id, (x, y)));
         }
+        for &(id, _) in &locs {
+            if let Some(winfo) = self.find_win_mut(id) {
+                winfo.floating = true;
+            }
+        }
+        self.windows_loc.extend(locs);
         Ok(())
     }
 
-    // commit changes (synchronous)
-    /// Applies all pending changes (e.g., moves, resizes, visibility, and z-index updates)
-    /// and performs the sorting and re-stacking of windows.
-    pub fn commit(&mut self) -> Result<(), Error> {
-        let mut aux = ConfigureWindowAux::default();
-
-        // sort visible by zindex
-        let mut sorted_visible = self.visible_wins.values().collect::<Vec<_>>();
-        sorted_visible.sort_unstable_by_key(|v| v.index);
-
-        // push all hidden to bottom
-        aux = aux.stack_mode(StackMode::BELOW);
-        for wininfo in self.hidden_wins.values() {
-            let &(x, y) = self.windows_loc.get(&wininfo.id).unwrap_or(&(0, 0));
-            let &(w, h) = self.windows_size.get(&wininfo.id).unwrap_or(&(0, 0));
-            aux = aux.x(x).y(y).width(w).height(h);
-            self.conn.configure_window(wininfo.id, &aux)?;
+    /// Sets the layout used to tile the visible windows on every commit,
+    /// or stops tiling if `None` is given.
+    pub fn set_layout(&mut self, spec: Option<LayoutSpec>) -> Result<(), Error> {
+        if let Some(spec) = &spec {
+            if let Layout::MasterStack { ratio, masters } = spec.layout {
+                if !(ratio > 0.0 && ratio < 1.0) || masters == 0 {
+                    let msg = "master-stack needs a ratio between 0 and 1 and at least one master";
+                    return Err(ErrorKind::InvalidRequest(msg.to_owned()).into());
+                }
+            }
+            if spec.gaps > u32::from(u16::MAX) {
+                let msg = format!("gaps of {} pixels are larger than any screen", spec.gaps);
+                return Err(ErrorKind::InvalidRequest(msg).into());
+            }
+            self.reference_area(spec.output.as_deref())?;
         }
 
-        // push virtual root window
-        aux = aux.stack_mode(StackMode::ABOVE);
-        self.conn.configure_window(self.virtual_root_win, &aux)?;
+        self.layout = spec;
+        Ok(())
+    }
 
-        // stack sorted visible windows above it
-        for wininfo in sorted_visible {
-            let &(x, y) = self.windows_loc.get(&wininfo.id).unwrap_or(&(0, 0));
-            let &(w, h) = self.windows_size.get(&wininfo.id).unwrap_or(&(0, 0));
-            aux = aux.x(x).y(y).width(w).height(h);
-            self.conn.configure_window(wininfo.id, &aux)?;
+    /// Sets how ConfigureRequests of the given window are dealt with, or of all windows without a
+    /// policy of their own if no window is given. Passing `None` makes the window fall back to
+    /// the global policy, or resets the global policy to allowing all requests.
+    /// Returns `false` if the window is not managed.
+    pub fn set_configure_policy(
+        &mut self,
+        id: Option<Window>,
+        policy: Option<ConfigurePolicy>,
+    ) -> bool {
+        match id {
+            Some(id) => match self.find_win_mut(id) {
+                Some(winfo) => {
+                    winfo.configure_policy = policy;
+                    true
+                }
+                None => false,
+            },
+            None => {
+                self.configure_policy = policy.unwrap_or_default();
+                true
+            }
         }
+    }
 
-        self.conn.flush()?;
+    /// Sets whether windows that are withdrawn, i.e. unmapped by their client, are remembered
+    /// until they are destroyed. A remembered window that is mapped again returns to the stack,
+    /// z-index, workspace, layer and geometry it had, which take effect on the next commit.
+    /// Otherwise it is treated like any new window. Remembering is on by default.
+    pub fn set_remember_withdrawn(&mut self, remember: bool) {
+        self.remember_withdrawn = remember;
+        if !remember {
+            self.withdrawn_wins.clear();
+        }
+    }
 
+    /// Makes the named workspace the current one, creating it if it does not exist yet.
+    /// The windows of the previous workspace are hidden once the change is committed.
+    pub fn switch_workspace(&mut self, name: &str) -> Result<(), Error> {
+        self.current_workspace = self.workspace_index(name)?;
         Ok(())
     }
 
-    // check for newly discovered/mapped windows, sorted by recency,
-    // with most recent windows frist
-    /// Checks for newly discovered or mapped windows since the last query.
-    /// Returns a list of new windows along with their positions and dimensions.
-    pub fn check_new(&mut self) -> Vec<WindowInfo> {
-        // new windows only go into hidden_wins
-        let mut new_wins = self
-            .hidden_wins
-            .values()
-            .filter(|winfo| winfo.discovery_time >= self.last_discovery_time)
-            .map(|winfo| {
-                let id = winfo.id;
-                let (x, y) = self.windows_loc.get(&id).cloned().unwrap_or((0, 0));
-                let (width, height) = self.windows_size.get(&id).cloned().unwrap_or((0, 0));
-                WindowInfo {
-                    id,
-                    x,
-                    y,
-                    width,
-                    height,
+    /// Moves the specified windows to the named workspaces, creating those that do not exist yet.
+    /// Windows keep their z-index and whether they are visible within their workspace.
+    /// Returns a list of windows that were moved.
+    pub fn move_to_workspace<I, T>(&mut self, iter: I) -> Result<Vec<Window>, Error>
+    where
+        I: Iterator<Item = T>,
+        T: Into<WinWorkspace>,
+    {
+        let mut moved_wins = Vec::new();
+
+        for item in iter {
+            let WinWorkspace { id, workspace } = item.into();
+            let workspace = self.workspace_index(&workspace)?;
+            let now = self.next_update_time();
+            if let Some(winfo) = self.find_win_mut(id) {
+                if winfo.workspace != workspace {
+                    winfo.workspace = workspace;
+                    winfo.last_update_time = now;
+                    moved_wins.push(id);
                 }
-            })
-            .collect::<Vec<_>>();
+            }
+        }
 
-        new_wins.sort_unstable_by_key(|w| {
-            cmp::Reverse(
-                self.hidden_wins
-                    .get(&w.id)
-                    .and_then(|win_info| Some(win_info.index))
-                    .unwrap_or(0),
-            )
-        });
-        self.last_discovery_time = Instant::now();
-        new_wins
+        Ok(moved_wins)
     }
 
-    /// Returns a list of visible windows with their positions and dimensions as WindowInfo.
-    pub fn get_visible_wins(&self) -> Vec<WindowInfo> {
-        self.visible_wins
-            .values()
-            .map(|winfo| {
-                let id = winfo.id;
-                let (x, y) = self.windows_loc.get(&id).cloned().unwrap_or((0, 0));
-                let (width, height) = self.windows_size.get(&id).cloned().unwrap_or((0, 0));
-                WindowInfo {
-                    id,
-                    x,
-                    y,
-                    width,
-                    height,
-                }
+    /// Returns the workspaces along with the windows on each of them.
+    pub fn list_workspaces(&self) -> Vec<WorkspaceInfo> {
+        let by_index = |wins: &HashMap<Window, WinInfo>, workspace: usize| {
+            let mut wins = wins
+                .values()
+                .filter(|winfo| winfo.workspace == workspace)
+                .collect::<Vec<_>>();
+            wins.sort_by_key(|winfo| stack_key(winfo));
+            wins.iter().map(|winfo| winfo.id).collect()
+        };
+
+        self.workspaces
+            .iter()
+            .enumerate()
+            .map(|(workspace, name)| WorkspaceInfo {
+                name: name.clone(),
+                current: workspace == self.current_workspace,
+                visible: by_index(&self.visible_wins, workspace),
+                hidden: by_index(&self.hidden_wins, workspace),
             })
             .collect()
     }
 
-    /// Returns a list of hidden windows with their positions and dimensions as WindowInfo.
-    pub fn get_hidden_wins(&self) -> Vec<WindowInfo> {
-        self.hidden_wins
-            .values()
-            .map(|winfo| {
-                let id = winfo.id;
-                let (x, y) = self.windows_loc.get(&id).cloned().unwrap_or((0, 0));
-                let (width, height) = self.windows_size.get(&id).cloned().unwrap_or((0, 0));
-                WindowInfo {
-                    id,
-                    x,
-                    y,
-                    width,
-                    height,
-                }
-            })
-            .collect()
+    /// Replaces all rules, e.g. with those loaded from a configuration file.
+    pub fn set_rules(&mut self, rules: RuleSet) {
+        self.rules = rules;
     }
 
-    fn screen_ref(&self) -> &Screen {
-        &self.conn.setup().roots[self.screen_num]
+    /// Adds a rule applied to windows mapped from now on,
+    /// replacing the rule with the same name if there is any.
+    pub fn add_rule(&mut self, rule: Rule) -> Result<(), Error> {
+        self.rules.add(rule)
     }
 
-    fn become_wm(&self) -> Result<(), Error> {
-        let mask = EventMask::SUBSTRUCTURE_REDIRECT
-            | EventMask::SUBSTRUCTURE_NOTIFY
-            | EventMask::ENTER_WINDOW;
+    /// Removes the rule with the given name. Returns `false` if there was no such rule.
+    pub fn remove_rule(&mut self, name: &str) -> bool {
+        self.rules.remove(name)
+    }
 
-        let change = ChangeWindowAttributesAux::default().event_mask(mask);
+    /// Returns the rules in the order they are applied.
+    pub fn list_rules(&self) -> Vec<Rule> {
+        self.rules.list()
+    }
 
-        let root = self.screen_ref().root;
+    /// Replaces the scene store, e.g. with one persisted to disk.
+    pub fn set_scenes(&mut self, scenes: SceneStore) {
+        self.scenes = scenes;
+    }
 
-        self.conn.change_window_attributes(root, &change)?.check()?;
+    /// Saves the visibility, z-index and geometry of the given windows as a named scene,
+    /// replacing any scene with the same name. Defaults to all windows of the current workspace.
+    pub fn save_scene(&mut self, name: &str, windows: Option<Vec<Window>>) -> Result<(), Error> {
+        if name.is_empty() {
+            let msg = "scene names must not be empty".to_owned();
+            return Err(ErrorKind::InvalidRequest(msg).into());
+        }
 
-        Ok(())
-    }
+        let mut wins = match windows {
+            Some(windows) => windows
+                .into_iter()
+                .filter_map(|id| self.find_win(id))
+                .collect::<Vec<_>>(),
+            None => self
+                .visible_wins
+                .values()
+                .chain(self.hidden_wins.values())
+                .filter(|winfo| winfo.workspace == self.current_workspace)
+                .collect(),
+        };
+        wins.sort_by_key(|winfo| stack_key(winfo));
+        wins.dedup_by_key(|winfo| winfo.id);
 
-    fn scan_windows(&mut self) -> Result<(), Error> {
-        let tree_reply = self.conn.query_tree(self.screen_ref().root)?.reply()?;
+        let windows = wins
+            .into_iter()
+            .map(|winfo| {
+                let info = self.window_info(winfo.id);
+                SceneWindow {
+                    class: info.properties.class,
+                    instance: info.properties.instance,
+                    title: info.properties.title,
+                    visible: self.visible_wins.contains_key(&winfo.id),
+                    zindex: winfo.index,
+                    x: info.x,
+                    y: info.y,
+                    width: info.width,
+                    height: info.height,
+                }
+            })
+            .collect();
 
-        // For each window, request its attributes and geometry *now*
-        let mut cookies = Vec::with_capacity(tree_reply.children.len());
-        for win in tree_reply.children {
-            let attr = self.conn.get_window_attributes(win)?;
-            cookies.push((win, attr));
-        }
+        self.scenes.save(Scene {
+            name: name.to_owned(),
+            windows,
+        })
+    }
 
-        // Get the replies and manage windows
-        let mut resp = Vec::with_capacity(cookies.len());
-        for (win, attr) in cookies {
-            if let Ok(attr) = attr.reply() {
-                resp.push((win, attr));
+    /// Queues the visibility, z-index and geometry saved in the named scene for the matching
+    /// windows of the current workspace. Windows that are not part of the scene are left alone.
+    /// The changes will only take effect after the `commit` method is called.
+    /// Returns a list of windows that matched.
+    pub fn apply_scene(&mut self, name: &str) -> Result<Vec<Window>, Error> {
+        let scene = match self.scenes.get(name) {
+            Some(scene) => scene.clone(),
+            None => {
+                let msg = format!("unknown scene {}", name);
+                return Err(ErrorKind::InvalidRequest(msg).into());
             }
-        }
-
-        let wins = &mut self.hidden_wins;
+        };
 
-        let vroot_win = self.virtual_root_win;
+        let mut candidates = self
+            .visible_wins
+            .values()
+            .chain(self.hidden_wins.values())
+            .filter(|winfo| winfo.workspace == self.current_workspace)
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|winfo| winfo.discovery_time);
+        let candidates = candidates
+            .into_iter()
+            .map(|winfo| (winfo.id, winfo.properties.clone()))
+            .collect::<Vec<_>>();
 
-        for (win, attr) in resp {
-            // ignore virtual_root_win or unmapped windows or windows with override-redirect set
-            if win != vroot_win && !attr.override_redirect && attr.map_state != MapState::UNMAPPED {
-                wins.entry(win).or_insert(WinInfo {
-                    id: win,
-                    index: 0,
-                    discovery_time: Instant::now(),
-                    last_update_time: Instant::now(),
-                });
+        let matched = match_windows(&scene, &candidates);
+
+        let mut applied = Vec::with_capacity(matched.len());
+        for (id, entry) in matched {
+            self.change_visiblity(std::iter::once(WinVisbilty {
+                id,
+                visible: entry.visible,
+            }));
+            self.change_indices(std::iter::once(WinZIndex {
+                id,
+                zindex: entry.zindex,
+            }));
+            if let Some(winfo) = self.find_win_mut(id) {
+                winfo.floating = true;
             }
+            self.windows_loc.insert(id, (entry.x, entry.y));
+            self.stage_size(id, (entry.width, entry.height), false);
+            applied.push(id);
         }
 
-        Ok(())
+        Ok(applied)
     }
 
-    fn handle_configure_request(&mut self, event: ConfigureRequestEvent) -> Result<(), Error> {
-        let mut aux = ConfigureWindowAux::default();
+    /// Returns the saved scenes.
+    pub fn list_scenes(&self) -> Vec<Scene> {
+        self.scenes.list()
+    }
 
-        let x: u16 = ConfigWindow::X.into();
-        let y: u16 = ConfigWindow::Y.into();
-        let w: u16 = ConfigWindow::WIDTH.into();
-        let h: u16 = ConfigWindow::HEIGHT.into();
+    /// Deletes the named scene. Returns `false` if there was no such scene.
+    pub fn delete_scene(&mut self, name: &str) -> Result<bool, Error> {
+        self.scenes.delete(name)
+    }
 
-        let event_mask: u16 = event.value_mask.into();
+    /// Validates, queues and commits the target state of several windows at once. Nothing is
+    /// changed if any entry is invalid, and the server is grabbed while committing so that no
+    /// half-applied state is ever shown. Any changes queued beforehand are committed along with
+    /// the layout. Unlike moves and resizes, the placed windows are not exempted from tiling.
+    /// Returns what the commit did, as `commit` does.
+    pub fn apply_layout<I, T>(&mut self, iter: I) -> Result<CommitReport, Error>
+    where
+        I: Iterator<Item = T>,
+        T: Into<WinLayout>,
+    {
+        let mut staged = Vec::new();
+        for item in iter {
+            let WinLayout {
+                id,
+                output,
+                visible,
+                zindex,
+                x,
+                y,
+                width,
+                height,
+                ignore_hints,
+            } = item.into();
 
-        if event_mask & x != 0 {
-            aux = aux.x(i32::from(event.x));
-        }
-        if event_mask & y != 0 {
-            aux = aux.y(i32::from(event.y));
-        }
-        if event_mask & w != 0 {
-            aux = aux.width(u32::from(event.width));
-        }
-        if event_mask & h != 0 {
-            aux = aux.height(u32::from(event.height));
+            if self.find_win(id).is_none() {
+                let msg = format!("unknown window {}", id);
+                return Err(ErrorKind::InvalidRequest(msg).into());
+            }
+
+            let (area_x, area_y, area_w, area_h) = self.reference_area(output.as_deref())?;
+            let x = x
+                .map(|x| resolve_position(id, area_x, &x, area_w))
+                .transpose()?;
+            let y = y
+                .map(|y| resolve_position(id, area_y, &y, area_h))
+                .transpose()?;
+            let width = width
+                .map(|width| resolve_size(id, &width, area_w))
+                .transpose()?;
+            let height = height
+                .map(|height| resolve_size(id, &height, area_h))
+                .transpose()?;
+
+            staged.push((id, visible, zindex, (x, y), (width, height), ignore_hints));
         }
 
-        aux = aux.stack_mode(StackMode::BELOW);
+        for (id, visible, zindex, (x, y), (width, height), ignore_hints) in staged {
+            if let Some(visible) = visible {
+                self.change_visiblity(std::iter::once(WinVisbilty { id, visible }));
+            }
+            if let Some(zindex) = zindex {
+                self.change_indices(std::iter::once(WinZIndex { id, zindex }));
+            }
 
-        if event_mask & x != 0 && event_mask & y != 0 {
-            self.windows_loc
-                .insert(event.window, (i32::from(event.x), i32::from(event.y)));
+            let (loc, size) = {
+                let info = self.window_info(id);
+                ((info.x, info.y), (info.width, info.height))
+            };
+            if x.is_some() || y.is_some() {
+                let loc = (x.unwrap_or(loc.0), y.unwrap_or(loc.1));
+                self.windows_loc.insert(id, loc);
+            }
+            if width.is_some() || height.is_some() {
+                let size = (width.unwrap_or(size.0), height.unwrap_or(size.1));
+                self.stage_size(id, size, ignore_hints);
+            }
         }
 
-        if event_mask & w != 0 && event_mask & h != 0 {
-            self.windows_size.insert(
-                event.window,
-                (u32::from(event.width), u32::from(event.height)),
-            );
+        self.conn.grab_server()?;
+        let committed = self.commit();
+        // always let go of the server, even if committing failed
+        self.conn.ungrab_server()?;
+        self.conn.flush()?;
+
+        committed
+    }
+
+    /// Returns what the next commit would change, compared to the state as of the last commit.
+    pub fn get_pending_changes(&self) -> PendingChanges {
+        let (sorted_visible, _) = self.commit_order();
+        let tiled = self.tiled_geometry(&sorted_visible);
+
+        let mut wins = self
+            .visible_wins
+            .values()
+            .chain(self.hidden_wins.values())
+            .collect::<Vec<_>>();
+        wins.sort_by_key(|winfo| winfo.discovery_time);
+
+        let windows = wins
+            .into_iter()
+            .filter_map(|winfo| {
+                let staged = self.staged_state(winfo);
+                let committed = winfo.committed;
+                let (loc, size) = self.target_geometry(winfo.id, &tiled);
+                let (loc, size) = (loc.unwrap_or(winfo.loc), size.unwrap_or(winfo.size));
+
+                let changes = WinChanges {
+                    id: winfo.id,
+                    visible: change(committed.visible, staged.visible),
+                    zindex: change(committed.index, staged.index),
+                    workspace: change(committed.workspace, staged.workspace)
+                        .map(|c| self.workspace_change(c)),
+                    layer: change(
+                        implied_layer(committed.layer, &winfo.properties),
+                        implied_layer(staged.layer, &winfo.properties),
+                    ),
+                    x: change(winfo.loc.0, loc.0),
+                    y: change(winfo.loc.1, loc.1),
+                    width: change(winfo.size.0, size.0),
+                    height: change(winfo.size.1, size.1),
+                };
+
+                let changed = changes.visible.is_some()
+                    || changes.zindex.is_some()
+                    || changes.workspace.is_some()
+                    || changes.layer.is_some()
+                    || changes.x.is_some()
+                    || changes.y.is_some()
+                    || changes.width.is_some()
+                    || changes.height.is_some();
+                changed.then_some(changes)
+            })
+            .collect();
+
+        PendingChanges {
+            workspace: change(self.committed_workspace, self.current_workspace)
+                .map(|c| self.workspace_change(c)),
+            windows,
+        }
+    }
+
+    /// Drops all queued moves and resizes, and restores the visibility, z-index, workspace, layer
+    /// and floating of every window, as well as the current workspace, to what they were at the
+    /// last commit.
+    pub fn discard_pending_changes(&mut self) {
+        let wins = self
+            .visible_wins
+            .drain()
+            .chain(self.hidden_wins.drain())
+            .map(|(_, winfo)| winfo)
+            .collect::<Vec<_>>();
+
+        for mut winfo in wins {
+            winfo.index = winfo.committed.index;
+            winfo.workspace = winfo.committed.workspace;
+            winfo.layer = winfo.committed.layer;
+            winfo.floating = winfo.committed.floating;
+            if winfo.committed.visible {
+                self.visible_wins.insert(winfo.id, winfo);
+            } else {
+                self.hidden_wins.insert(winfo.id, winfo);
+            }
+        }
+
+        self.windows_loc.clear();
+        self.windows_size.clear();
+        self.unhinted_sizes.clear();
+        self.current_workspace = self.committed_workspace;
+    }
+
+    // commit changes (synchronous)
+    /// Applies all pending changes (e.g., moves, resizes, visibility, and z-index updates)
+    /// and performs the sorting and re-stacking of windows.
+    /// The resulting state is recorded so that it can be returned to with `undo` and `redo`.
+    /// Windows that cannot be configured, e.g. because they were destroyed in the meantime, are
+    /// reported along with the error, while the rest are committed regardless. Windows that no
+    /// longer exist are dropped. The geometry applied to moved or resized windows is reported too.
+    pub fn commit(&mut self) -> Result<CommitReport, Error> {
+        let report = self.commit_staged()?;
+        self.record_history();
+        Ok(report)
+    }
+
+    /// Returns to the state as of the commit before the current one, dropping any pending
+    /// changes. Windows that have been unmapped since are skipped.
+    /// Returns the windows that were restored, or `None` if there is nothing to undo.
+    pub fn undo(&mut self) -> Result<Option<Vec<Window>>, Error> {
+        match self.history.undo() {
+            Some(entry) => {
+                let entry = entry.clone();
+                self.restore(entry).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Returns to the state that was last undone, dropping any pending changes. Windows that
+    /// have been unmapped since are skipped.
+    /// Returns the windows that were restored, or `None` if there is nothing to redo.
+    pub fn redo(&mut self) -> Result<Option<Vec<Window>>, Error> {
+        match self.history.redo() {
+            Some(entry) => {
+                let entry = entry.clone();
+                self.restore(entry).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the recorded states, oldest first.
+    pub fn list_history(&self) -> Vec<HistoryEntry> {
+        self.history
+            .iter()
+            .map(|(current, entry)| HistoryEntry {
+                current,
+                ..entry.clone()
+            })
+            .collect()
+    }
+
+    // stage the state of a history entry for the windows that are still around and commit it,
+    // without recording a new entry
+    fn restore(&mut self, entry: HistoryEntry) -> Result<Vec<Window>, Error> {
+        self.discard_pending_changes();
+        self.current_workspace = self.workspace_index(&entry.workspace)?;
+
+        let mut restored = Vec::with_capacity(entry.windows.len());
+        for win in entry.windows {
+            let workspace = self.workspace_index(&win.workspace)?;
+            let winfo = match self.find_win_mut(win.id) {
+                Some(winfo) => winfo,
+                None => continue,
+            };
+            winfo.workspace = workspace;
+
+            self.change_visiblity(std::iter::once(WinVisbilty {
+                id: win.id,
+                visible: win.visible,
+            }));
+            self.change_indices(std::iter::once(WinZIndex {
+                id: win.id,
+                zindex: win.zindex,
+            }));
+            // the recorded sizes were applied before, so restore them exactly
+            self.windows_loc.insert(win.id, (win.x, win.y));
+            self.stage_size(win.id, (win.width, win.height), true);
+            restored.push(win.id);
+        }
+
+        let report = self.commit_staged()?;
+        restored.retain(|&id| !report.failures.iter().any(|failure| failure.id == id));
+        Ok(restored)
+    }
+
+    // remember the state of every window as just committed
+    fn record_history(&mut self) {
+        let mut wins = self
+            .visible_wins
+            .values()
+            .chain(self.hidden_wins.values())
+            .collect::<Vec<_>>();
+        wins.sort_by_key(|winfo| winfo.discovery_time);
+
+        let windows = wins
+            .into_iter()
+            .map(|winfo| WinSnapshot {
+                id: winfo.id,
+                visible: self.visible_wins.contains_key(&winfo.id),
+                zindex: winfo.index,
+                workspace: self.workspaces[winfo.workspace].clone(),
+                x: winfo.loc.0,
+                y: winfo.loc.1,
+                width: winfo.size.0,
+                height: winfo.size.1,
+            })
+            .collect();
+
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        self.history.record(HistoryEntry {
+            time,
+            current: false,
+            workspace: self.workspaces[self.current_workspace].clone(),
+            windows,
+        });
+    }
+
+    fn commit_staged(&mut self) -> Result<CommitReport, Error> {
+        let (requests, mut stacking) = self.plan_commit();
+
+        // the cookies borrow the connection, which would otherwise keep `self` borrowed
+        let conn = self.conn.clone();
+        let mut cookies = Vec::with_capacity(requests.len());
+        let mut geometries = Vec::new();
+
+        for op in &requests {
+            let stack_mode = match op.stack_mode {
+                StackPosition::Above => StackMode::ABOVE,
+                StackPosition::Below => StackMode::BELOW,
+            };
+            let aux = ConfigureWindowAux {
+                x: op.x,
+                y: op.y,
+                width: op.width,
+                height: op.height,
+                stack_mode: Some(stack_mode),
+                ..Default::default()
+            };
+            cookies.push((op.id, conn.configure_window(op.id, &aux)?));
+
+            // ask for the geometry of moved and resized windows as it ends up on the server
+            if op.x.is_some() || op.y.is_some() || op.width.is_some() || op.height.is_some() {
+                geometries.push((op.id, conn.get_geometry(op.id)?));
+            }
+
+            // the new geometry is assumed to be applied from here on,
+            // ConfigureNotify will correct it otherwise
+            if let Some(winfo) = self.find_win_mut(op.id) {
+                winfo.loc = (op.x.unwrap_or(winfo.loc.0), op.y.unwrap_or(winfo.loc.1));
+                winfo.size = (
+                    op.width.unwrap_or(winfo.size.0),
+                    op.height.unwrap_or(winfo.size.1),
+                );
+            }
+        }
+
+        // all requests are sent before checking any, so this waits on the server only once
+        let mut failures = Vec::new();
+        let mut vanished = Vec::new();
+        for (id, cookie) in cookies {
+            if let Err(err) = cookie.check() {
+                let err = Error::from(err);
+                if err.is_fatal() {
+                    return Err(err);
+                }
+                if err.vanished_window().is_some() {
+                    vanished.push(id);
+                }
+                failures.push(WinFailure {
+                    id,
+                    error: err.to_string(),
+                });
+            }
+        }
+
+        let mut applied = Vec::new();
+        for (id, cookie) in geometries {
+            if failures.iter().any(|failure| failure.id == id) {
+                continue;
+            }
+            let reply = match cookie.reply() {
+                Ok(reply) => reply,
+                Err(err) => {
+                    let err = Error::from(err);
+                    if err.is_fatal() {
+                        return Err(err);
+                    }
+                    if err.vanished_window().is_some() {
+                        vanished.push(id);
+                    }
+                    failures.push(WinFailure {
+                        id,
+                        error: err.to_string(),
+                    });
+                    continue;
+                }
+            };
+            let geometry = WinGeometry {
+                id,
+                x: reply.x.into(),
+                y: reply.y.into(),
+                width: reply.width.into(),
+                height: reply.height.into(),
+            };
+            if let Some(winfo) = self.find_win_mut(id) {
+                winfo.loc = (geometry.x, geometry.y);
+                winfo.size = (geometry.width, geometry.height);
+            }
+            applied.push(geometry);
+        }
+
+        // windows that still exist are kept, only their failure is reported
+        for id in vanished {
+            stacking.retain(|&win| win != id);
+            self.drop_window(id)?;
+        }
+
+        self.stacking = stacking;
+        self.update_client_lists()?;
+        self.update_desktops()?;
+
+        self.conn.flush()?;
+
+        self.windows_loc.clear();
+        self.windows_size.clear();
+        self.unhinted_sizes.clear();
+        self.mark_committed();
+
+        self.publish(Event::Committed);
+
+        Ok(CommitReport { failures, applied })
+    }
+
+    /// Returns the ConfigureWindow requests the next commit would issue, in order,
+    /// without changing anything.
+    pub fn dry_run_commit(&self) -> Vec<ConfigureOp> {
+        self.plan_commit().0
+    }
+
+    // The requests a commit issues along with the resulting stacking order from bottom to top.
+    // Hidden windows go below the virtual root, then visible windows are stacked above it.
+    fn plan_commit(&self) -> (Vec<ConfigureOp>, Vec<Window>) {
+        let (sorted_visible, hidden) = self.commit_order();
+        let tiled = self.tiled_geometry(&sorted_visible);
+
+        let mut requests = Vec::with_capacity(hidden.len() + sorted_visible.len() + 1);
+
+        // push all hidden to bottom, along with everything on other workspaces
+        for &id in &hidden {
+            requests.push(self.configure_op(id, &tiled, StackPosition::Below));
+        }
+
+        // push virtual root window
+        requests.push(self.configure_op(self.virtual_root_win, &tiled, StackPosition::Above));
+
+        // stack sorted visible windows above it
+        for &id in &sorted_visible {
+            requests.push(self.configure_op(id, &tiled, StackPosition::Above));
+        }
+
+        let stacking = stacking::stacking_order(&sorted_visible, &hidden);
+
+        (requests, stacking)
+    }
+
+    // visible windows of the current workspace from bottom to top by layer and z-index, and all
+    // other windows from top to bottom, as they are pushed to the bottom one after another
+    fn commit_order(&self) -> (Vec<Window>, Vec<Window>) {
+        let entries = self
+            .visible_wins
+            .values()
+            .chain(self.hidden_wins.values())
+            .map(|winfo| stack_entry(winfo, self.is_shown(winfo.id)))
+            .collect::<Vec<_>>();
+        stacking::commit_order(&entries)
+    }
+
+    // geometry of the visible windows according to the layout, if one is set
+    fn tiled_geometry(&self, sorted_visible: &[Window]) -> HashMap<Window, Area> {
+        let spec = match &self.layout {
+            Some(spec) => spec,
+            None => return HashMap::new(),
+        };
+
+        // highest z-index comes first, i.e. becomes the master;
+        // windows outside of the normal layer, such as docks, are never tiled
+        let tiled = sorted_visible
+            .iter()
+            .rev()
+            .map(|id| &self.visible_wins[id])
+            .filter(|winfo| layer_of(winfo) == StackLayer::Normal)
+            .filter(|winfo| !(spec.float_manual && winfo.floating))
+            .map(|winfo| winfo.id)
+            .collect::<Vec<_>>();
+
+        // the output may have gone away since the layout was set, fall back to the screen
+        let (x, y, w, h) = self.reference_area(spec.output.as_deref()).unwrap_or((
+            0,
+            0,
+            self.screen_size.0,
+            self.screen_size.1,
+        ));
+
+        let areas = arrange(&spec.layout, ((x, y), (w, h)), spec.gaps, tiled.len());
+        tiled.into_iter().zip(areas).collect()
+    }
+
+    // geometry a window will have after the commit, if it is to change:
+    // laid out by the layout, or as queued by a move or resize, and adjusted to its size hints
+    fn target_geometry(&self, id: Window, tiled: &HashMap<Window, Area>) -> PendingGeometry {
+        let (loc, size) = match tiled.get(&id) {
+            Some(&(loc, size)) => (Some(loc), Some(size)),
+            None => (
+                self.windows_loc.get(&id).cloned(),
+                self.windows_size.get(&id).cloned(),
+            ),
+        };
+
+        let winfo = match self.find_win(id) {
+            Some(winfo) => winfo,
+            None => return (loc, size),
+        };
+        match size {
+            Some(requested) if !self.unhinted_sizes.contains(&id) => {
+                let size = winfo.hints.constrain(requested);
+                let loc = if size != requested {
+                    let loc = loc.unwrap_or(winfo.loc);
+                    Some(winfo.hints.gravitate(loc, requested, size))
+                } else {
+                    loc
+                };
+                (loc, Some(size))
+            }
+            _ => (loc, size),
+        }
+    }
+
+    // queue a resize, to be adjusted to the window's size hints unless they are to be ignored
+    fn stage_size(&mut self, id: Window, size: (u32, u32), ignore_hints: bool) {
+        self.windows_size.insert(id, size);
+        if ignore_hints {
+            self.unhinted_sizes.insert(id);
+        } else {
+            self.unhinted_sizes.remove(&id);
+        }
+    }
+
+    // a restacking request, along with only those fields of the pending geometry
+    // that differ from the window's current geometry
+    fn configure_op(
+        &self,
+        id: Window,
+        tiled: &HashMap<Window, Area>,
+        stack_mode: StackPosition,
+    ) -> ConfigureOp {
+        let mut op = ConfigureOp {
+            id,
+            x: None,
+            y: None,
+            width: None,
+            height: None,
+            stack_mode,
+        };
+
+        if let Some(winfo) = self.find_win(id) {
+            let (loc, size) = self.target_geometry(id, tiled);
+            if let Some((x, y)) = loc {
+                op.x = (x != winfo.loc.0).then_some(x);
+                op.y = (y != winfo.loc.1).then_some(y);
+            }
+            if let Some((w, h)) = size {
+                op.width = (w != winfo.size.0).then_some(w);
+                op.height = (h != winfo.size.1).then_some(h);
+            }
+        }
+
+        op
+    }
+
+    // remember the visibility, z-index, workspace, layer and floating of every window as committed
+    fn mark_committed(&mut self) {
+        for winfo in self.visible_wins.values_mut() {
+            winfo.committed = WinState {
+                visible: true,
+                index: winfo.index,
+                workspace: winfo.workspace,
+                layer: winfo.layer,
+                floating: winfo.floating,
+            };
+        }
+        for winfo in self.hidden_wins.values_mut() {
+            winfo.committed = WinState {
+                visible: false,
+                index: winfo.index,
+                workspace: winfo.workspace,
+                layer: winfo.layer,
+                floating: winfo.floating,
+            };
+        }
+        self.committed_workspace = self.current_workspace;
+    }
+
+    fn staged_state(&self, winfo: &WinInfo) -> WinState {
+        WinState {
+            visible: self.visible_wins.contains_key(&winfo.id),
+            index: winfo.index,
+            workspace: winfo.workspace,
+            layer: winfo.layer,
+            floating: winfo.floating,
+        }
+    }
+
+    fn workspace_change(&self, change: Change<usize>) -> Change<String> {
+        Change {
+            from: self.workspaces[change.from].clone(),
+            to: self.workspaces[change.to].clone(),
+        }
+    }
+
+    // check for newly discovered/mapped windows, sorted by recency,
+    // with most recent windows frist
+    /// Checks for newly discovered or mapped windows since the last query.
+    /// Returns a list of new windows along with their positions and dimensions.
+    pub fn check_new(&mut self) -> Vec<WindowInfo> {
+        // new windows go into hidden_wins, unless a rule made them visible
+        let mut new_wins = self
+            .hidden_wins
+            .values()
+            .chain(self.visible_wins.values())
+            .filter(|winfo| winfo.discovery_time >= self.last_discovery_time)
+            .map(|winfo| self.window_info(winfo.id))
+            .collect::<Vec<_>>();
+
+        new_wins.sort_by_key(|w| cmp::Reverse(self.find_win(w.id).map(stack_key)));
+        self.last_discovery_time = Instant::now();
+        new_wins
+    }
+
+    /// Returns a list of visible windows of the current workspace with their positions and
+    /// dimensions as WindowInfo.
+    pub fn get_visible_wins(&self) -> Vec<WindowInfo> {
+        self.visible_wins
+            .values()
+            .filter(|winfo| winfo.workspace == self.current_workspace)
+            .map(|winfo| self.window_info(winfo.id))
+            .collect()
+    }
+
+    /// Returns a list of hidden windows of the current workspace with their positions and
+    /// dimensions as WindowInfo.
+    pub fn get_hidden_wins(&self) -> Vec<WindowInfo> {
+        self.hidden_wins
+            .values()
+            .filter(|winfo| winfo.workspace == self.current_workspace)
+            .map(|winfo| self.window_info(winfo.id))
+            .collect()
+    }
+
+    fn window_info(&self, id: Window) -> WindowInfo {
+        let winfo = self.find_win(id);
+        let (x, y) = self
+            .windows_loc
+            .get(&id)
+            .cloned()
+            .or_else(|| winfo.map(|winfo| winfo.loc))
+            .unwrap_or((0, 0));
+        let (width, height) = self
+            .windows_size
+            .get(&id)
+            .cloned()
+            .or_else(|| winfo.map(|winfo| winfo.size))
+            .unwrap_or((0, 0));
+        let properties = winfo
+            .map(|winfo| winfo.properties.clone())
+            .unwrap_or_default();
+        WindowInfo {
+            id,
+            x,
+            y,
+            width,
+            height,
+            properties,
+        }
+    }
+
+    // origin and size of the area that relative lengths refer to: an output or the whole screen
+    fn reference_area(&self, output: Option<&str>) -> Result<(i32, i32, u32, u32), Error> {
+        match output {
+            Some(name) => self
+                .outputs
+                .iter()
+                .find(|o| o.name == name)
+                .map(|o| (o.x, o.y, o.width, o.height))
+                .ok_or_else(|| {
+                    ErrorKind::InvalidRequest(format!("unknown output {}", name)).into()
+                }),
+            None => Ok((0, 0, self.screen_size.0, self.screen_size.1)),
+        }
+    }
+
+    // index of the named workspace, which is created if needed
+    fn workspace_index(&mut self, name: &str) -> Result<usize, Error> {
+        if name.is_empty() {
+            let msg = "workspace names must not be empty".to_owned();
+            return Err(ErrorKind::InvalidRequest(msg).into());
+        }
+
+        let index = match self.workspaces.iter().position(|w| w == name) {
+            Some(index) => index,
+            None => {
+                self.workspaces.push(name.to_owned());
+                self.workspaces.len() - 1
+            }
+        };
+        Ok(index)
+    }
+
+    // the other windows in the same list, workspace and layer as the window, from bottom to top
+    fn stack_peers(&self, id: Window) -> Option<Vec<Window>> {
+        let winfo = self.find_win(id)?;
+        let list = if self.visible_wins.contains_key(&id) {
+            &self.visible_wins
+        } else {
+            &self.hidden_wins
+        };
+
+        let mut peers = list
+            .values()
+            .filter(|peer| peer.id != id && peer.workspace == winfo.workspace)
+            .filter(|peer| layer_of(peer) == layer_of(winfo))
+            .collect::<Vec<_>>();
+        peers.sort_by_key(|peer| stack_key(peer));
+        Some(peers.into_iter().map(|peer| peer.id).collect())
+    }
+
+    // Move a window directly above the sibling, or below all of its peers without one, by giving
+    // it the z-index of the sibling (or of the lowest peer) and updating it last. Peers with that
+    // z-index that are to stay above it are updated again afterwards, in their current order.
+    fn place_above(&mut self, id: Window, sibling: Option<Window>, peers: &[Window]) {
+        let (reference, above) = match sibling {
+            Some(sibling) => match peers.iter().position(|&peer| peer == sibling) {
+                Some(pos) => (sibling, &peers[pos + 1..]),
+                None => return,
+            },
+            None => match peers.first() {
+                Some(&bottom) => (bottom, peers),
+                None => return,
+            },
+        };
+        let index = match self.zindex_of(reference) {
+            Some(index) => index,
+            None => return,
+        };
+
+        let now = self.next_update_time();
+        if let Some(winfo) = self.find_win_mut(id) {
+            winfo.index = index;
+            winfo.last_update_time = now;
+        }
+
+        for &peer in above {
+            let now = self.next_update_time();
+            if let Some(winfo) = self.find_win_mut(peer) {
+                if winfo.index == index {
+                    winfo.last_update_time = now;
+                }
+            }
+        }
+    }
+
+    // A time for a window update that is later than all those handed out before, even if the
+    // clock has not advanced since, so that ties in z-index are always broken the same way.
+    fn next_update_time(&mut self) -> Instant {
+        let now = Instant::now();
+        self.last_update_time = if now > self.last_update_time {
+            now
+        } else {
+            self.last_update_time + Duration::from_nanos(1)
+        };
+        self.last_update_time
+    }
+
+    fn zindex_of(&self, id: Window) -> Option<ZIndexType> {
+        self.find_win(id).map(|winfo| winfo.index)
+    }
+
+    // whether the window is visible on the current workspace
+    fn is_shown(&self, id: Window) -> bool {
+        self.visible_wins
+            .get(&id)
+            .map(|winfo| winfo.workspace == self.current_workspace)
+            .unwrap_or(false)
+    }
+
+    fn find_win(&self, id: Window) -> Option<&WinInfo> {
+        self.visible_wins
+            .get(&id)
+            .or_else(|| self.hidden_wins.get(&id))
+    }
+
+    fn find_win_mut(&mut self, id: Window) -> Option<&mut WinInfo> {
+        match self.visible_wins.get_mut(&id) {
+            Some(winfo) => Some(winfo),
+            None => self.hidden_wins.get_mut(&id),
+        }
+    }
+
+    // start managing a window, new windows always go into the hidden list
+    fn track_window(&mut self, win: Window) -> Result<(), Error> {
+        if self.find_win(win).is_some() {
+            return Ok(());
+        }
+
+        // keep informed about changes to the window's metadata
+        let change = ChangeWindowAttributesAux::default().event_mask(EventMask::PROPERTY_CHANGE);
+        self.conn.change_window_attributes(win, &change)?;
+
+        let geometry = self.conn.get_geometry(win)?;
+        let properties = query_properties(&self.conn, &self.atoms, win)?;
+        let hints = query_size_hints(&self.conn, win)?;
+        let geometry = geometry.reply()?;
+
+        // freshly (re)mapped windows sit on top of the stack
+        self.stacking.push(win);
+
+        let now = self.next_update_time();
+
+        self.hidden_wins.insert(
+            win,
+            WinInfo {
+                id: win,
+                index: 0,
+                discovery_time: Instant::now(),
+                last_update_time: now,
+                properties,
+                hints,
+                loc: (i32::from(geometry.x), i32::from(geometry.y)),
+                size: (u32::from(geometry.width), u32::from(geometry.height)),
+                floating: false,
+                workspace: self.current_workspace,
+                layer: None,
+                configure_policy: None,
+                iconic: false,
+                committed: WinState {
+                    visible: false,
+                    index: 0,
+                    workspace: self.current_workspace,
+                    layer: None,
+                    floating: false,
+                },
+            },
+        );
+
+        ewmh::set_window_desktop(&self.conn, &self.atoms, win, self.current_workspace)?;
+        ewmh::set_wm_state(&self.conn, &self.atoms, win, NORMAL_STATE)?;
+
+        Ok(())
+    }
+
+    // put a withdrawn window that is mapped again back where it was, returning false if it was
+    // not remembered
+    fn restore_withdrawn(&mut self, win: Window) -> Result<bool, Error> {
+        let (visible, mut winfo) = match self.withdrawn_wins.remove(&win) {
+            Some(withdrawn) => withdrawn,
+            None => return Ok(false),
+        };
+
+        // the client may have changed its window while it was withdrawn
+        let geometry = self.conn.get_geometry(win)?;
+        winfo.properties = query_properties(&self.conn, &self.atoms, win)?;
+        winfo.hints = query_size_hints(&self.conn, win)?;
+        let geometry = geometry.reply()?;
+
+        // move it back to where it was on the next commit
+        let remembered = (winfo.loc, winfo.size);
+        winfo.loc = (i32::from(geometry.x), i32::from(geometry.y));
+        winfo.size = (u32::from(geometry.width), u32::from(geometry.height));
+        if winfo.loc != remembered.0 {
+            self.windows_loc.insert(win, remembered.0);
+        }
+        if winfo.size != remembered.1 {
+            self.stage_size(win, remembered.1, true);
+        }
+
+        winfo.iconic = false;
+        let workspace = winfo.workspace;
+
+        // like any freshly mapped window, it sits on top of the stack until the next commit
+        self.stacking.push(win);
+        if visible {
+            self.visible_wins.insert(win, winfo);
+        } else {
+            self.hidden_wins.insert(win, winfo);
+        }
+
+        ewmh::set_window_desktop(&self.conn, &self.atoms, win, workspace)?;
+        ewmh::set_wm_state(&self.conn, &self.atoms, win, NORMAL_STATE)?;
+
+        Ok(true)
+    }
+
+    // stop managing a window, returning whether it was in the visible stack along with what was
+    // known about it
+    fn forget_window(&mut self, win: Window) -> Option<(bool, WinInfo)> {
+        self.windows_loc.remove(&win);
+        self.windows_size.remove(&win);
+        self.unhinted_sizes.remove(&win);
+
+        let forgotten = match self.visible_wins.remove(&win) {
+            Some(winfo) => Some((true, winfo)),
+            None => self.hidden_wins.remove(&win).map(|winfo| (false, winfo)),
+        };
+        if forgotten.is_some() {
+            self.stacking.retain(|&w| w != win);
+        }
+        forgotten
+    }
+
+    // deliver event to all subscribers, forgetting those that have gone away
+    fn publish(&mut self, event: Event) {
+        self.subscribers.retain(|tx| tx.send(event.clone()).is_ok());
+        #[cfg(feature = "async")]
+        self.async_subscribers
+            .retain(|tx| tx.send(event.clone()).is_ok());
+    }
+
+    fn screen_ref(&self) -> &Screen {
+        &self.conn.setup().roots[self.screen_num]
+    }
+
+    // whether WM_DELETE_WINDOW is listed in the window's WM_PROTOCOLS
+    fn supports_delete_window(&self, id: Window) -> Result<bool, Error> {
+        let reply = self
+            .conn
+            .get_property(false, id, self.atoms.WM_PROTOCOLS, AtomEnum::ATOM, 0, 64)?
+            .reply()?;
+
+        let supported = reply
+            .value32()
+            .map(|mut protocols| protocols.any(|atom| atom == self.atoms.WM_DELETE_WINDOW))
+            .unwrap_or(false);
+
+        Ok(supported)
+    }
+
+    fn update_client_lists(&self) -> Result<(), Error> {
+        let mut clients = self
+            .visible_wins
+            .values()
+            .chain(self.hidden_wins.values())
+            .collect::<Vec<_>>();
+        clients.sort_by_key(|winfo| winfo.discovery_time);
+        let clients = clients.iter().map(|winfo| winfo.id).collect::<Vec<_>>();
+
+        let root = self.screen_ref().root;
+        ewmh::set_client_lists(&self.conn, &self.atoms, root, &clients, &self.stacking)
+    }
+
+    fn update_desktops(&self) -> Result<(), Error> {
+        let root = self.screen_ref().root;
+        ewmh::set_desktops(
+            &self.conn,
+            &self.atoms,
+            root,
+            &self.workspaces,
+            self.current_workspace,
+        )?;
+
+        for winfo in self.visible_wins.values().chain(self.hidden_wins.values()) {
+            ewmh::set_window_desktop(&self.conn, &self.atoms, winfo.id, winfo.workspace)?;
         }
+        Ok(())
+    }
+
+    fn become_wm(&mut self) -> Result<(), Error> {
+        let mask = EventMask::SUBSTRUCTURE_REDIRECT
+            | EventMask::SUBSTRUCTURE_NOTIFY
+            | EventMask::ENTER_WINDOW;
+
+        let change = ChangeWindowAttributesAux::default().event_mask(mask);
 
-        self.conn.configure_window(event.window, &aux)?;
+        let root = self.screen_ref().root;
+
+        self.conn.change_window_attributes(root, &change)?.check()?;
+
+        // now that we own the root window, let everyone know
+        ewmh::announce_wm(&self.conn, &self.atoms, root)?;
 
         Ok(())
     }
 
+    fn init_outputs(&mut self) -> Result<(), Error> {
+        let root = self.screen_ref().root;
+        if init_randr(&self.conn, root)? {
+            self.outputs = query_outputs(&self.conn, root)?;
+        }
+        Ok(())
+    }
+
+    // re-read the outputs, announcing those that were plugged, unplugged or changed
+    fn refresh_outputs(&mut self) -> Result<(), Error> {
+        let outputs = query_outputs(&self.conn, self.screen_ref().root)?;
+        let old_outputs = std::mem::replace(&mut self.outputs, outputs.clone());
+
+        for output in &old_outputs {
+            if !outputs.iter().any(|o| o.name == output.name) {
+                self.publish(Event::OutputDisconnected(output.name.clone()));
+            }
+        }
+
+        for output in outputs {
+            match old_outputs.iter().find(|o| o.name == output.name) {
+                None => self.publish(Event::OutputConnected(output)),
+                Some(old_output) if *old_output != output => {
+                    self.publish(Event::OutputChanged(output))
+                }
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn scan_windows(&mut self) -> Result<(), Error> {
+        let tree_reply = self.conn.query_tree(self.screen_ref().root)?.reply()?;
+
+        // For each window, request its attributes and geometry *now*
+        let mut cookies = Vec::with_capacity(tree_reply.children.len());
+        for win in tree_reply.children {
+            let attr = self.conn.get_window_attributes(win)?;
+            cookies.push((win, attr));
+        }
+
+        // Get the replies and manage windows
+        let mut resp = Vec::with_capacity(cookies.len());
+        for (win, attr) in cookies {
+            if let Ok(attr) = attr.reply() {
+                resp.push((win, attr));
+            }
+        }
+
+        let vroot_win = self.virtual_root_win;
+
+        for (win, attr) in resp {
+            // ignore virtual_root_win or unmapped windows or windows with override-redirect set
+            if win != vroot_win && !attr.override_redirect && attr.map_state != MapState::UNMAPPED {
+                self.track_window(win)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_configure_request(&mut self, event: ConfigureRequestEvent) -> Result<(), Error> {
+        let x: u16 = ConfigWindow::X.into();
+        let y: u16 = ConfigWindow::Y.into();
+        let w: u16 = ConfigWindow::WIDTH.into();
+        let h: u16 = ConfigWindow::HEIGHT.into();
+
+        let event_mask: u16 = event.value_mask.into();
+
+        let requested = WinConfigure {
+            id: event.window,
+            x: (event_mask & x != 0).then(|| i32::from(event.x)),
+            y: (event_mask & y != 0).then(|| i32::from(event.y)),
+            width: (event_mask & w != 0).then(|| u32::from(event.width)),
+            height: (event_mask & h != 0).then(|| u32::from(event.height)),
+        };
+
+        // geometry the window is to have once pending changes are committed
+        let current = self.find_win(event.window).map(|winfo| {
+            (
+                winfo.configure_policy.unwrap_or(self.configure_policy),
+                self.windows_loc
+                    .get(&winfo.id)
+                    .cloned()
+                    .unwrap_or(winfo.loc),
+                self.windows_size
+                    .get(&winfo.id)
+                    .cloned()
+                    .unwrap_or(winfo.size),
+            )
+        });
+
+        // windows that are not managed get whatever they ask for
+        let (policy, granted) = match current {
+            Some((policy, loc, size)) => {
+                let output = self.output_area_of(loc, size);
+                (policy, policy.grant(&requested, (loc, size), output))
+            }
+            None => (ConfigurePolicy::Allow, requested.clone()),
+        };
+
+        let aux = ConfigureWindowAux {
+            x: granted.x,
+            y: granted.y,
+            width: granted.width,
+            height: granted.height,
+            ..Default::default()
+        };
+
+        // merge whatever part of the geometry was granted with what is already known
+        if let Some((_, (loc_x, loc_y), (size_w, size_h))) = current {
+            if granted.x.is_some() || granted.y.is_some() {
+                let new_loc = (granted.x.unwrap_or(loc_x), granted.y.unwrap_or(loc_y));
+                self.windows_loc.insert(event.window, new_loc);
+            }
+            if granted.width.is_some() || granted.height.is_some() {
+                let new_size = (
+                    granted.width.unwrap_or(size_w),
+                    granted.height.unwrap_or(size_h),
+                );
+                self.stage_size(event.window, new_size, false);
+            }
+        }
+
+        if granted.x.is_some()
+            || granted.y.is_some()
+            || granted.width.is_some()
+            || granted.height.is_some()
+        {
+            self.conn.configure_window(event.window, &aux)?;
+        }
+
+        if granted != requested {
+            // per ICCCM, a client whose request was not granted as asked is told where its
+            // window actually is with a synthetic ConfigureNotify
+            if let Some(winfo) = self.find_win(event.window) {
+                let (loc_x, loc_y) = winfo.loc;
+                let (size_w, size_h) = winfo.size;
+
+                let notify = ConfigureNotifyEvent {
+                    response_type: CONFIGURE_NOTIFY_EVENT,
+                    sequence: 0,
+                    event: event.window,
+                    window: event.window,
+                    above_sibling: x11rb::NONE,
+                    x: granted.x.unwrap_or(loc_x) as i16,
+                    y: granted.y.unwrap_or(loc_y) as i16,
+                    width: granted.width.unwrap_or(size_w) as u16,
+                    height: granted.height.unwrap_or(size_h) as u16,
+                    border_width: event.border_width,
+                    override_redirect: false,
+                };
+                self.conn
+                    .send_event(false, event.window, EventMask::STRUCTURE_NOTIFY, notify)?;
+            }
+
+            self.publish(Event::ConfigureDenied(ConfigureDenial {
+                policy,
+                requested: requested.clone(),
+                granted,
+            }));
+        }
+
+        self.publish(Event::ConfigureRequested(requested));
+
+        Ok(())
+    }
+
+    // area of the output that the centre of the given geometry lies on, or else the screen
+    fn output_area_of(&self, (x, y): (i32, i32), (w, h): (u32, u32)) -> Area {
+        let centre_x = i64::from(x) + i64::from(w) / 2;
+        let centre_y = i64::from(y) + i64::from(h) / 2;
+        self.outputs
+            .iter()
+            .find(|o| {
+                (i64::from(o.x)..i64::from(o.x) + i64::from(o.width)).contains(&centre_x)
+                    && (i64::from(o.y)..i64::from(o.y) + i64::from(o.height)).contains(&centre_y)
+            })
+            .map(|o| ((o.x, o.y), (o.width, o.height)))
+            .unwrap_or(((0, 0), self.screen_size))
+    }
+
     fn handle_map_request(&mut self, event: MapRequestEvent) -> Result<(), Error> {
         let win = event.window;
 
-        // track window
-        self.hidden_wins.entry(win).or_insert(WinInfo {
-            id: win,
-            index: 0,
-            discovery_time: Instant::now(),
-            last_update_time: Instant::now(),
-        });
+        let actions = if let Some(winfo) = self.find_win_mut(win) {
+            // an iconified window is shown again in the place it kept
+            winfo.iconic = false;
+            ewmh::set_wm_state(&self.conn, &self.atoms, win, NORMAL_STATE)?;
+            None
+        } else if self.restore_withdrawn(win)? {
+            None
+        } else {
+            self.track_window(win)?;
+
+            let actions = self
+                .find_win(win)
+                .and_then(|winfo| self.rules.actions_for(&winfo.properties));
+            if let Some(actions) = &actions {
+                // a rule must never keep a window from being mapped
+                if let Err(err) = self.apply_rule_actions(win, actions) {
+                    if err.is_fatal() || err.vanished_window().is_some() {
+                        return Err(err);
+                    }
+                    eprintln!("failed to apply rules to window {}: {}", win, err);
+                }
+            }
+            actions
+        };
+
+        self.update_client_lists()?;
 
         self.conn.map_window(win)?;
 
+        if actions.and_then(|actions| actions.focus).unwrap_or(false) {
+            self.focus_window(win)?;
+        }
+
+        self.publish(Event::WindowMapped(self.window_info(win)));
+
+        Ok(())
+    }
+
+    // place a window that is about to be mapped as the matching rules say, skipping any action
+    // that cannot be carried out
+    fn apply_rule_actions(&mut self, win: Window, actions: &RuleActions) -> Result<(), Error> {
+        let workspace = actions
+            .workspace
+            .as_ref()
+            .and_then(|name| skip_invalid(win, self.workspace_index(name)));
+
+        // geometry is relative to the output, falling back to the screen if it is gone
+        let (area_x, area_y, area_w, area_h) = self
+            .reference_area(actions.output.as_deref())
+            .unwrap_or((0, 0, self.screen_size.0, self.screen_size.1));
+        let x = actions
+            .x
+            .and_then(|x| skip_invalid(win, resolve_position(win, area_x, &x, area_w)));
+        let y = actions
+            .y
+            .and_then(|y| skip_invalid(win, resolve_position(win, area_y, &y, area_h)));
+        let width = actions
+            .width
+            .and_then(|width| skip_invalid(win, resolve_size(win, &width, area_w)));
+        let height = actions
+            .height
+            .and_then(|height| skip_invalid(win, resolve_size(win, &height, area_h)));
+
+        let mut winfo = match self
+            .hidden_wins
+            .remove(&win)
+            .or_else(|| self.visible_wins.remove(&win))
+        {
+            Some(winfo) => winfo,
+            None => return Ok(()),
+        };
+
+        if let Some(workspace) = workspace {
+            winfo.workspace = workspace;
+        }
+        if let Some(zindex) = actions.zindex {
+            winfo.index = zindex;
+        }
+
+        let mut aux = ConfigureWindowAux::default();
+        if x.is_some() || y.is_some() {
+            winfo.loc = (x.unwrap_or(winfo.loc.0), y.unwrap_or(winfo.loc.1));
+            aux = aux.x(winfo.loc.0).y(winfo.loc.1);
+            winfo.floating = true;
+        }
+        if width.is_some() || height.is_some() {
+            winfo.size = (
+                width.unwrap_or(winfo.size.0),
+                height.unwrap_or(winfo.size.1),
+            );
+            aux = aux.width(winfo.size.0).height(winfo.size.1);
+            winfo.floating = true;
+        }
+
+        // rules take effect right away, so this is what is committed now
+        let workspace = winfo.workspace;
+        winfo.committed = WinState {
+            visible: actions.visible.unwrap_or(false),
+            index: winfo.index,
+            workspace,
+            layer: winfo.layer,
+            floating: winfo.floating,
+        };
+        if actions.visible.unwrap_or(false) {
+            self.visible_wins.insert(win, winfo);
+        } else {
+            self.hidden_wins.insert(win, winfo);
+        }
+
+        // slot the window in among the shown windows by z-index, or below everything
+        self.stacking.retain(|&id| id != win);
+        if self.is_shown(win) {
+            let order = |winfo: &WinInfo| (layer_of(winfo), winfo.index);
+            let key = order(&self.visible_wins[&win]);
+            let above = self
+                .stacking
+                .iter()
+                .position(|id| self.is_shown(*id) && order(&self.visible_wins[id]) > key);
+            match above {
+                Some(pos) => {
+                    aux = aux.sibling(self.stacking[pos]).stack_mode(StackMode::BELOW);
+                    self.stacking.insert(pos, win);
+                }
+                None => {
+                    aux = aux.stack_mode(StackMode::ABOVE);
+                    self.stacking.push(win);
+                }
+            }
+        } else {
+            aux = aux.stack_mode(StackMode::BELOW);
+            self.stacking.insert(0, win);
+        }
+
+        self.conn.configure_window(win, &aux)?;
+        ewmh::set_window_desktop(&self.conn, &self.atoms, win, workspace)?;
+
         Ok(())
     }
 
     fn handle_unmap_notify(&mut self, event: UnmapNotifyEvent) -> Result<(), Error> {
-        self.hidden_wins.remove(&event.window);
-        self.visible_wins.remove(&event.window);
-        self.windows_loc.remove(&event.window);
-        self.windows_size.remove(&event.window);
+        let win = event.window;
+        let iconic = match self.find_win(win) {
+            Some(winfo) => winfo.iconic,
+            None => return Ok(()),
+        };
+
+        // the window manager unmaps iconified windows itself, which leaves them managed. Clients
+        // withdraw an iconified window with a synthetic UnmapNotify, as ICCCM asks of them.
+        let synthetic = event.response_type & 0x80 != 0;
+        if iconic && !synthetic {
+            self.publish(Event::WindowIconified(win));
+            return Ok(());
+        }
+
+        if let Some(forgotten) = self.forget_window(win) {
+            if self.remember_withdrawn {
+                self.withdrawn_wins.insert(win, forgotten);
+            }
+
+            // the window may already be gone, in which case the error is ignored
+            self.conn.delete_property(win, self.atoms.WM_STATE)?;
+            self.update_client_lists()?;
+            self.clear_active_window(win)?;
+
+            self.publish(Event::WindowUnmapped(win));
+        }
+
+        Ok(())
+    }
+
+    fn handle_destroy_notify(&mut self, event: DestroyNotifyEvent) -> Result<(), Error> {
+        // windows are unmapped before they are destroyed, so this mostly forgets withdrawn ones
+        self.drop_window(event.window)
+    }
+
+    fn handle_reparent_notify(&mut self, event: ReparentNotifyEvent) -> Result<(), Error> {
+        // a window that is reparented onto the root is managed once it is mapped, but one taken
+        // away from the root, e.g. to be embedded, is no longer ours to manage
+        if event.parent == self.screen_ref().root {
+            return Ok(());
+        }
+
+        self.drop_window(event.window)
+    }
+
+    // forget everything about a window that is gone for good
+    fn drop_window(&mut self, win: Window) -> Result<(), Error> {
+        self.withdrawn_wins.remove(&win);
+
+        if self.forget_window(win).is_some() {
+            self.update_client_lists()?;
+            self.clear_active_window(win)?;
+            self.publish(Event::WindowUnmapped(win));
+        }
+
+        Ok(())
+    }
+
+    // no window is active anymore once the focused one is gone
+    fn clear_active_window(&mut self, win: Window) -> Result<(), Error> {
+        if self.active_win == Some(win) {
+            self.active_win = None;
+            ewmh::set_active_window(&self.conn, &self.atoms, self.screen_ref().root, x11rb::NONE)?;
+        }
+        Ok(())
+    }
+
+    fn handle_configure_notify(&mut self, event: ConfigureNotifyEvent) -> Result<(), Error> {
+        if let Some(winfo) = self.find_win_mut(event.window) {
+            winfo.loc = (i32::from(event.x), i32::from(event.y));
+            winfo.size = (u32::from(event.width), u32::from(event.height));
+        }
+        Ok(())
+    }
+
+    fn handle_screen_change_notify(&mut self, event: ScreenChangeNotifyEvent) -> Result<(), Error> {
+        if event.root != self.screen_ref().root {
+            return Ok(());
+        }
+
+        // the reported size does not account for the screen being rotated
+        let (width, height) = if is_sideways(event.rotation) {
+            (event.height, event.width)
+        } else {
+            (event.width, event.height)
+        };
+
+        self.screen_size = (u32::from(width), u32::from(height));
+
+        // keep the virtual root covering the whole screen
+        let aux = ConfigureWindowAux::default()
+            .width(self.screen_size.0)
+            .height(self.screen_size.1);
+        self.conn.configure_window(self.virtual_root_win, &aux)?;
+
+        self.refresh_outputs()
+    }
+
+    fn handle_client_message(&mut self, event: ClientMessageEvent) -> Result<(), Error> {
+        // clients ask for their _NET_WM_STATE to be changed, which is then picked up from the
+        // updated property through PropertyNotify
+        if event.type_ == self.atoms._NET_WM_STATE
+            && event.format == 32
+            && self.find_win(event.window).is_some()
+        {
+            let data = event.data.as_data32();
+            ewmh::change_window_state(
+                &self.conn,
+                &self.atoms,
+                event.window,
+                data[0],
+                [data[1], data[2]],
+            )?;
+        }
+
+        // clients ask to be iconified with WM_CHANGE_STATE, upon which the window is unmapped
+        // but stays managed
+        if event.type_ == self.atoms.WM_CHANGE_STATE
+            && event.format == 32
+            && event.data.as_data32()[0] == ICONIC_STATE
+        {
+            if let Some(winfo) = self.find_win_mut(event.window) {
+                if !winfo.iconic {
+                    winfo.iconic = true;
+                    ewmh::set_wm_state(&self.conn, &self.atoms, event.window, ICONIC_STATE)?;
+                    self.conn.unmap_window(event.window)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_property_notify(&mut self, event: PropertyNotifyEvent) -> Result<(), Error> {
+        if event.atom == u32::from(AtomEnum::WM_NORMAL_HINTS)
+            && self.find_win(event.window).is_some()
+        {
+            let hints = query_size_hints(&self.conn, event.window)?;
+            if let Some(winfo) = self.find_win_mut(event.window) {
+                winfo.hints = hints;
+            }
+            return Ok(());
+        }
+
+        if !is_tracked_property(&self.atoms, event.atom) || self.find_win(event.window).is_none() {
+            return Ok(());
+        }
+
+        let properties = query_properties(&self.conn, &self.atoms, event.window)?;
+        if let Some(winfo) = self.find_win_mut(event.window) {
+            winfo.properties = properties;
+        }
+
         Ok(())
     }
 
-    fn handle_event(&mut self, event: Event) -> Result<bool, Error> {
+    /// Handles a single X11 event.
+    /// Errors that only affect a single window or event are recovered from, dropping windows
+    /// that have vanished, so only fatal errors are returned.
+    pub fn handle_event(&mut self, event: XEvent) -> Result<(), Error> {
+        match self.dispatch_event(event) {
+            Ok(()) => Ok(()),
+            Err(err) => self.recover(err),
+        }
+    }
+
+    // carry on after an error unless it is fatal
+    fn recover(&mut self, err: Error) -> Result<(), Error> {
+        match err.severity() {
+            Severity::Fatal => Err(err),
+            Severity::Window => match err.vanished_window() {
+                Some(win) => self.drop_window(win),
+                None => Ok(()),
+            },
+            Severity::Request => Ok(()),
+        }
+    }
+
+    fn dispatch_event(&mut self, event: XEvent) -> Result<(), Error> {
         match event {
-            Event::UnmapNotify(une) => {
+            XEvent::UnmapNotify(une) => {
                 self.handle_unmap_notify(une)?;
             }
-            Event::ConfigureRequest(cre) => {
+            XEvent::DestroyNotify(dne) => {
+                self.handle_destroy_notify(dne)?;
+            }
+            XEvent::ReparentNotify(rne) => {
+                self.handle_reparent_notify(rne)?;
+            }
+            XEvent::ConfigureRequest(cre) => {
                 self.handle_configure_request(cre)?;
             }
-            Event::MapRequest(mre) => {
+            XEvent::MapRequest(mre) => {
                 self.handle_map_request(mre)?;
             }
-            Event::ClientMessage(msg_event) => {
-                if msg_event.type_ == self.pending_input_atom {
-                    return Ok(false);
-                }
+            XEvent::ConfigureNotify(cne) => {
+                self.handle_configure_notify(cne)?;
+            }
+            XEvent::PropertyNotify(pne) => {
+                self.handle_property_notify(pne)?;
+            }
+            XEvent::RandrScreenChangeNotify(scne) => {
+                self.handle_screen_change_notify(scne)?;
+            }
+            XEvent::RandrNotify(_) => {
+                self.refresh_outputs()?;
+            }
+            XEvent::ClientMessage(msg_event) => {
+                self.handle_client_message(msg_event)?;
+            }
+            // errors of requests whose outcome was not checked
+            XEvent::Error(err) => {
+                self.recover(err.into())?;
             }
             _ => (),
         }
 
-        Ok(true)
+        // make sure any requests issued while handling the event reach the server
+        self.conn.flush()?;
+
+        Ok(())
+    }
+}
+
+// layer a window is stacked in while it is shown
+fn layer_of(winfo: &WinInfo) -> StackLayer {
+    implied_layer(winfo.layer, &winfo.properties)
+}
+
+fn stack_entry(winfo: &WinInfo, shown: bool) -> StackEntry {
+    StackEntry {
+        id: winfo.id,
+        shown,
+        layer: layer_of(winfo),
+        zindex: winfo.index,
+        updated: winfo.last_update_time,
+        discovered: winfo.discovery_time,
+    }
+}
+
+// order of windows within a stack from bottom to top
+fn stack_key(winfo: &WinInfo) -> (ZIndexType, Instant, Instant, Window) {
+    stack_entry(winfo, false).key()
+}
+
+fn invalid_size(id: Window) -> Error {
+    ErrorKind::InvalidRequest(format!("invalid size for window {}", id)).into()
+}
+
+fn invalid_position(id: Window) -> Error {
+    ErrorKind::InvalidRequest(format!("invalid position for window {}", id)).into()
+}
+
+// a position relative to `origin`, which X11 takes as a 16-bit signed integer
+fn resolve_position(id: Window, origin: i32, pos: &Length, total: u32) -> Result<i32, Error> {
+    i64::from(origin)
+        .checked_add(pos.resolve(total))
+        .and_then(|pos| i16::try_from(pos).ok())
+        .map(i32::from)
+        .ok_or_else(|| invalid_position(id))
+}
+
+// a size, which X11 takes as a non-zero 16-bit unsigned integer
+fn resolve_size(id: Window, size: &Length, total: u32) -> Result<u32, Error> {
+    u16::try_from(size.resolve(total))
+        .ok()
+        .filter(|&size| size > 0)
+        .map(u32::from)
+        .ok_or_else(|| invalid_size(id))
+}
+
+// the value of a rule action, or None after logging why it is skipped
+fn skip_invalid<T>(win: Window, value: Result<T, Error>) -> Option<T> {
+    match value {
+        Ok(value) => Some(value),
+        Err(err) => {
+            eprintln!("skipping rule action for window {}: {}", win, err);
+            None
+        }
     }
 }
+
+// a change from one value to another, or None if they are the same
+fn change<T: PartialEq>(from: T, to: T) -> Option<Change<T>> {
+    (from != to).then(|| Change { from, to })
+}
@@ -0,0 +1,79 @@
+use x11rb::protocol::ErrorKind as X11ErrorKind;
+use x11rb::x11_utils::X11Error;
+use x11wmgr::messages::Rule;
+use x11wmgr::rules::RuleSet;
+use x11wmgr::{Error, Severity};
+
+fn x11_error(error_kind: X11ErrorKind, bad_value: u32) -> Error {
+    Error::from(X11Error {
+        error_kind,
+        error_code: 0,
+        sequence: 0,
+        bad_value,
+        minor_opcode: 0,
+        major_opcode: 0,
+        extension_name: None,
+        request_name: None,
+    })
+}
+
+#[test]
+fn test_vanished_window() {
+    let err = x11_error(X11ErrorKind::Window, 42);
+    assert_eq!(err.severity(), Severity::Window);
+    assert_eq!(err.vanished_window(), Some(42));
+    assert!(!err.is_fatal());
+}
+
+#[test]
+fn test_window_errors_without_vanished_window() {
+    let err = x11_error(X11ErrorKind::Match, 42);
+    assert_eq!(err.severity(), Severity::Window);
+    assert_eq!(err.vanished_window(), None);
+}
+
+#[test]
+fn test_other_x11_errors_only_fail_the_request() {
+    let err = x11_error(X11ErrorKind::Atom, 42);
+    assert_eq!(err.severity(), Severity::Request);
+    assert_eq!(err.vanished_window(), None);
+}
+
+#[test]
+fn test_lost_connection_is_fatal() {
+    let err = Error::from(x11rb::errors::ConnectionError::UnknownError);
+    assert_eq!(err.severity(), Severity::Fatal);
+    assert!(err.is_fatal());
+}
+
+#[test]
+fn test_x11_error_names_code_and_request() {
+    let err = Error::from(X11Error {
+        error_kind: X11ErrorKind::Window,
+        error_code: 3,
+        sequence: 0,
+        bad_value: 42,
+        minor_opcode: 0,
+        major_opcode: 12,
+        extension_name: None,
+        request_name: Some("ConfigureWindow"),
+    });
+    assert_eq!(err.to_string(), "X11 error 3 (Window) in ConfigureWindow");
+
+    let err = x11_error(X11ErrorKind::Atom, 42);
+    assert_eq!(err.to_string(), "X11 error 0 (Atom) in request 0.0");
+}
+
+#[test]
+fn test_invalid_request() {
+    let rule = Rule {
+        name: String::new(),
+        criteria: Default::default(),
+        actions: Default::default(),
+    };
+    let err = RuleSet::new().add(rule).unwrap_err();
+    assert!(err.is_invalid_request());
+    assert_eq!(err.severity(), Severity::Request);
+
+    assert!(!x11_error(X11ErrorKind::Atom, 42).is_invalid_request());
+}
@@ -0,0 +1,81 @@
+use x11rb::protocol::xproto::Gravity;
+use x11wmgr::hints::SizeHints;
+use x11wmgr::messages::{Length, Request, WinResize};
+
+#[test]
+fn test_constrain_without_hints() {
+    let hints = SizeHints::default();
+    assert_eq!(hints.constrain((640, 480)), (640, 480));
+    assert_eq!(hints.constrain((0, 0)), (1, 1));
+}
+
+#[test]
+fn test_constrain_min_max() {
+    let hints = SizeHints {
+        min_size: Some((200, 100)),
+        max_size: Some((800, 600)),
+        ..Default::default()
+    };
+    assert_eq!(hints.constrain((100, 50)), (200, 100));
+    assert_eq!(hints.constrain((1920, 1080)), (800, 600));
+    assert_eq!(hints.constrain((640, 480)), (640, 480));
+}
+
+#[test]
+fn test_constrain_increments() {
+    // a terminal with 7x14 cells and a 4 pixel border
+    let hints = SizeHints {
+        base_size: Some((4, 4)),
+        increment: Some((7, 14)),
+        ..Default::default()
+    };
+    assert_eq!(hints.constrain((960, 1080)), (956, 1068));
+    assert_eq!(hints.constrain((4 + 7 * 80, 4 + 14 * 24)), (564, 340));
+}
+
+#[test]
+fn test_constrain_aspect() {
+    let hints = SizeHints {
+        aspect: Some(((16, 9), (16, 9))),
+        ..Default::default()
+    };
+    assert_eq!(hints.constrain((1920, 1200)), (1920, 1080));
+    assert_eq!(hints.constrain((1920, 900)), (1600, 900));
+}
+
+#[test]
+fn test_gravitate() {
+    let mut hints = SizeHints::default();
+    assert_eq!(
+        hints.gravitate((100, 100), (500, 300), (400, 200)),
+        (100, 100)
+    );
+
+    hints.gravity = Gravity::SOUTH_EAST;
+    assert_eq!(
+        hints.gravitate((100, 100), (500, 300), (400, 200)),
+        (200, 200)
+    );
+
+    hints.gravity = Gravity::CENTER;
+    assert_eq!(
+        hints.gravitate((100, 100), (500, 300), (400, 200)),
+        (150, 150)
+    );
+}
+
+#[test]
+fn test_resize_ignore_hints_serialization() {
+    let request: Request = serde_json::from_str(
+        r#"{"ResizeWindows":[{"id":1,"width":800,"height":600,"ignore_hints":true}]}"#,
+    )
+    .unwrap();
+    let expected = Request::ResizeWindows(vec![WinResize {
+        id: 1,
+        output: None,
+        width: Length::Pixels(800),
+        height: Length::Pixels(600),
+        ignore_hints: true,
+    }]);
+    assert_eq!(request, expected);
+}
@@ -0,0 +1,82 @@
+use x11wmgr::history::History;
+use x11wmgr::messages::{HistoryEntry, Request, Response, WinSnapshot};
+
+#[test]
+fn test_history_undo_redo() {
+    let mut history = History::new(8);
+    assert_eq!(history.undo(), None);
+
+    history.record(1);
+    history.record(2);
+    history.record(3);
+
+    assert_eq!(history.undo(), Some(&2));
+    assert_eq!(history.undo(), Some(&1));
+    assert_eq!(history.undo(), None);
+    assert_eq!(history.current(), Some(&1));
+
+    assert_eq!(history.redo(), Some(&2));
+    assert_eq!(history.redo(), Some(&3));
+    assert_eq!(history.redo(), None);
+}
+
+#[test]
+fn test_history_record_drops_undone_states() {
+    let mut history = History::new(8);
+    history.record(1);
+    history.record(2);
+    history.record(3);
+    history.undo();
+    history.undo();
+
+    history.record(4);
+    assert_eq!(history.redo(), None);
+
+    let states = history.iter().collect::<Vec<_>>();
+    assert_eq!(states, vec![(false, &1), (true, &4)]);
+}
+
+#[test]
+fn test_history_is_bounded() {
+    let mut history = History::new(3);
+    for state in 1..=5 {
+        history.record(state);
+    }
+
+    assert_eq!(history.len(), 3);
+    assert_eq!(history.undo(), Some(&4));
+    assert_eq!(history.undo(), Some(&3));
+    assert_eq!(history.undo(), None);
+}
+
+#[test]
+fn test_history_serialization() {
+    let request: Request = serde_json::from_str(r#""Undo""#).unwrap();
+    assert_eq!(request, Request::Undo);
+
+    let response = Response::History(vec![HistoryEntry {
+        time: 1700000000,
+        current: true,
+        workspace: "1".to_owned(),
+        windows: vec![WinSnapshot {
+            id: 1,
+            visible: true,
+            zindex: 2,
+            workspace: "1".to_owned(),
+            x: 0,
+            y: 0,
+            width: 640,
+            height: 480,
+        }],
+    }]);
+
+    let serialized = serde_json::to_string(&response).unwrap();
+    let expected = r#"{"History":[{"time":1700000000,"current":true,"workspace":"1","windows":[{"id":1,"visible":true,"zindex":2,"workspace":"1","x":0,"y":0,"width":640,"height":480}]}]}"#;
+    assert_eq!(serialized, expected);
+
+    let deserialized: Response = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, response);
+
+    let serialized = serde_json::to_string(&Response::Redone(None)).unwrap();
+    assert_eq!(serialized, r#"{"Redone":null}"#);
+}
@@ -0,0 +1,126 @@
+use x11wmgr::layout::arrange;
+use x11wmgr::messages::{Layout, LayoutSpec, Request};
+
+const SCREEN: ((i32, i32), (u32, u32)) = ((0, 0), (1920, 1080));
+
+#[test]
+fn test_arrange_no_windows() {
+    assert!(arrange(&Layout::Grid, SCREEN, 0, 0).is_empty());
+}
+
+#[test]
+fn test_arrange_monocle() {
+    let areas = arrange(&Layout::Monocle, SCREEN, 10, 2);
+    assert_eq!(areas, vec![((10, 10), (1900, 1060)); 2]);
+}
+
+#[test]
+fn test_arrange_columns_with_gaps() {
+    let areas = arrange(&Layout::Columns, ((100, 0), (310, 100)), 10, 3);
+    assert_eq!(
+        areas,
+        vec![
+            ((110, 10), (90, 80)),
+            ((210, 10), (90, 80)),
+            ((310, 10), (90, 80)),
+        ]
+    );
+}
+
+#[test]
+fn test_arrange_rows_distributes_remainder() {
+    let areas = arrange(&Layout::Rows, ((0, 0), (100, 100)), 0, 3);
+    assert_eq!(
+        areas,
+        vec![
+            ((0, 0), (100, 34)),
+            ((0, 34), (100, 33)),
+            ((0, 67), (100, 33))
+        ]
+    );
+}
+
+#[test]
+fn test_arrange_grid() {
+    let areas = arrange(&Layout::Grid, SCREEN, 0, 3);
+    assert_eq!(
+        areas,
+        vec![
+            ((0, 0), (960, 540)),
+            ((960, 0), (960, 540)),
+            ((0, 540), (1920, 540)),
+        ]
+    );
+}
+
+#[test]
+fn test_arrange_master_stack() {
+    let layout = Layout::MasterStack {
+        ratio: 0.5,
+        masters: 1,
+    };
+    let areas = arrange(&layout, SCREEN, 0, 3);
+    assert_eq!(
+        areas,
+        vec![
+            ((0, 0), (960, 1080)),
+            ((960, 0), (960, 540)),
+            ((960, 540), (960, 540)),
+        ]
+    );
+
+    // a lone master takes the whole area
+    let areas = arrange(&layout, SCREEN, 0, 1);
+    assert_eq!(areas, vec![SCREEN]);
+}
+
+#[test]
+fn test_arrange_huge_gaps() {
+    // gaps larger than the area leave nothing to the windows, but never overflow
+    for layout in &[
+        Layout::Monocle,
+        Layout::Columns,
+        Layout::Rows,
+        Layout::Grid,
+        Layout::MasterStack {
+            ratio: 0.5,
+            masters: 1,
+        },
+    ] {
+        let areas = arrange(layout, SCREEN, 3_000_000_000, 2);
+        assert_eq!(areas.len(), 2);
+        assert!(areas.iter().all(|&(_, size)| size == (0, 0)));
+    }
+}
+
+#[test]
+fn test_request_set_layout_deserialization() {
+    let request: Request =
+        serde_json::from_str(r#"{"SetLayout":{"layout":{"MasterStack":{"ratio":0.6}},"gaps":4}}"#)
+            .unwrap();
+    let expected = Request::SetLayout(Some(LayoutSpec {
+        layout: Layout::MasterStack {
+            ratio: 0.6,
+            masters: 1,
+        },
+        gaps: 4,
+        output: None,
+        float_manual: false,
+    }));
+    assert_eq!(request, expected);
+
+    let request: Request = serde_json::from_str(r#"{"SetLayout":null}"#).unwrap();
+    assert_eq!(request, Request::SetLayout(None));
+}
+
+#[test]
+fn test_request_tile_windows_serialization() {
+    let request = Request::TileWindows(vec![1, 2]);
+
+    let serialized = serde_json::to_string(&request).unwrap();
+    let expected = r#"{"TileWindows":[1,2]}"#;
+    assert_eq!(serialized, expected);
+
+    let deserialized: Request = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, request);
+}
@@ -0,0 +1,103 @@
+use x11wmgr::messages::{ConfigureDenial, ConfigurePolicy, Event, Request, WinConfigure};
+
+const OUTPUT: ((i32, i32), (u32, u32)) = ((0, 0), (1920, 1080));
+const CURRENT: ((i32, i32), (u32, u32)) = ((100, 100), (640, 480));
+
+fn request(
+    x: Option<i32>,
+    y: Option<i32>,
+    width: Option<u32>,
+    height: Option<u32>,
+) -> WinConfigure {
+    WinConfigure {
+        id: 1,
+        x,
+        y,
+        width,
+        height,
+    }
+}
+
+#[test]
+fn test_allow_grants_everything() {
+    let req = request(Some(-50), Some(20), Some(3000), None);
+    assert_eq!(ConfigurePolicy::Allow.grant(&req, CURRENT, OUTPUT), req);
+}
+
+#[test]
+fn test_deny_grants_nothing() {
+    let req = request(Some(-50), Some(20), Some(3000), Some(200));
+    let granted = ConfigurePolicy::Deny.grant(&req, CURRENT, OUTPUT);
+    assert_eq!(granted, request(None, None, None, None));
+}
+
+#[test]
+fn test_allow_size_only() {
+    let req = request(Some(-50), Some(20), Some(800), None);
+    let granted = ConfigurePolicy::AllowSizeOnly.grant(&req, CURRENT, OUTPUT);
+    assert_eq!(granted, request(None, None, Some(800), None));
+}
+
+#[test]
+fn test_clamp_to_output() {
+    let policy = ConfigurePolicy::ClampToOutput;
+
+    // requests that fit are granted as asked
+    let req = request(Some(200), None, Some(800), Some(600));
+    assert_eq!(policy.grant(&req, CURRENT, OUTPUT), req);
+
+    // too large and partially off the output
+    let req = request(Some(-50), Some(900), Some(3000), None);
+    let granted = policy.grant(&req, CURRENT, OUTPUT);
+    assert_eq!(granted, request(Some(0), Some(600), Some(1920), None));
+
+    // growing past the edge moves the window back onto the output
+    let req = request(None, None, Some(1900), None);
+    let granted = policy.grant(&req, CURRENT, OUTPUT);
+    assert_eq!(granted, request(Some(20), None, Some(1900), None));
+
+    // outputs need not start at the origin
+    let output = ((1920, 0), (1280, 1024));
+    let req = request(Some(3000), Some(0), None, None);
+    let granted = policy.grant(&req, ((2000, 100), (640, 480)), output);
+    assert_eq!(granted, request(Some(2560), Some(0), None, None));
+}
+
+#[test]
+fn test_request_set_configure_policy_serialization() {
+    let request = Request::SetConfigurePolicy {
+        id: Some(1),
+        policy: Some(ConfigurePolicy::AllowSizeOnly),
+    };
+
+    let serialized = serde_json::to_string(&request).unwrap();
+    let expected = r#"{"SetConfigurePolicy":{"id":1,"policy":"AllowSizeOnly"}}"#;
+    assert_eq!(serialized, expected);
+
+    let deserialized: Request = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, request);
+
+    // without an id, the global policy is set
+    let deserialized: Request =
+        serde_json::from_str(r#"{"SetConfigurePolicy":{"policy":"Deny"}}"#).unwrap();
+    assert_eq!(
+        deserialized,
+        Request::SetConfigurePolicy {
+            id: None,
+            policy: Some(ConfigurePolicy::Deny),
+        }
+    );
+}
+
+#[test]
+fn test_event_configure_denied_serialization() {
+    let event = Event::ConfigureDenied(ConfigureDenial {
+        policy: ConfigurePolicy::Deny,
+        requested: request(Some(10), None, None, None),
+        granted: request(None, None, None, None),
+    });
+
+    let serialized = serde_json::to_string(&event).unwrap();
+    let deserialized: Event = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, event);
+}
@@ -0,0 +1,237 @@
+use x11wmgr::messages::{Length, Request, Rule, RuleActions, RuleMatch, WindowProperties};
+use x11wmgr::rules::RuleSet;
+
+fn firefox() -> WindowProperties {
+    WindowProperties {
+        title: Some("Mozilla Firefox - Private Browsing".to_owned()),
+        class: Some("firefox".to_owned()),
+        instance: Some("Navigator".to_owned()),
+        ..Default::default()
+    }
+}
+
+fn rule(name: &str, criteria: RuleMatch, actions: RuleActions) -> Rule {
+    Rule {
+        name: name.to_owned(),
+        criteria,
+        actions,
+    }
+}
+
+#[test]
+fn test_rule_deserialization() {
+    let request: Request = serde_json::from_str(
+        r#"{"AddRule":{"name":"browser","match":{"class":"firefox","title":"Private"},"actions":{"visible":true,"zindex":5,"workspace":"web","x":0,"width":"50%","focus":true}}}"#,
+    )
+    .unwrap();
+
+    let expected = Request::AddRule(Box::new(rule(
+        "browser",
+        RuleMatch {
+            class: Some("firefox".to_owned()),
+            title: Some("Private".to_owned()),
+            ..Default::default()
+        },
+        RuleActions {
+            visible: Some(true),
+            zindex: Some(5),
+            workspace: Some("web".to_owned()),
+            x: Some(Length::Pixels(0)),
+            width: Some(Length::Percent(50.0)),
+            focus: Some(true),
+            ..Default::default()
+        },
+    )));
+    assert_eq!(request, expected);
+}
+
+#[test]
+fn test_rules_match_class_and_title() {
+    let mut rules = RuleSet::new();
+    rules
+        .add(rule(
+            "private",
+            RuleMatch {
+                class: Some("firefox".to_owned()),
+                title: Some("Private Browsing$".to_owned()),
+                ..Default::default()
+            },
+            RuleActions {
+                workspace: Some("web".to_owned()),
+                ..Default::default()
+            },
+        ))
+        .unwrap();
+    rules
+        .add(rule(
+            "terminal",
+            RuleMatch {
+                class: Some("xterm".to_owned()),
+                ..Default::default()
+            },
+            RuleActions {
+                visible: Some(true),
+                ..Default::default()
+            },
+        ))
+        .unwrap();
+
+    let actions = rules.actions_for(&firefox()).unwrap();
+    assert_eq!(actions.workspace.as_deref(), Some("web"));
+    assert_eq!(actions.visible, None);
+
+    let mut other = firefox();
+    other.title = Some("Mozilla Firefox".to_owned());
+    assert_eq!(rules.actions_for(&other), None);
+}
+
+#[test]
+fn test_rules_later_rules_override() {
+    let mut rules = RuleSet::new();
+    rules
+        .add(rule(
+            "everything",
+            RuleMatch::default(),
+            RuleActions {
+                visible: Some(true),
+                zindex: Some(1),
+                ..Default::default()
+            },
+        ))
+        .unwrap();
+    rules
+        .add(rule(
+            "dialogs",
+            RuleMatch {
+                window_type: Some("DIALOG".to_owned()),
+                ..Default::default()
+            },
+            RuleActions {
+                zindex: Some(10),
+                focus: Some(true),
+                ..Default::default()
+            },
+        ))
+        .unwrap();
+
+    let mut dialog = firefox();
+    dialog.window_type = vec!["DIALOG".to_owned()];
+
+    let actions = rules.actions_for(&dialog).unwrap();
+    assert_eq!(actions.visible, Some(true));
+    assert_eq!(actions.zindex, Some(10));
+    assert_eq!(actions.focus, Some(true));
+
+    let actions = rules.actions_for(&firefox()).unwrap();
+    assert_eq!(actions.zindex, Some(1));
+    assert_eq!(actions.focus, None);
+}
+
+#[test]
+fn test_rules_match_ancestor_pid() {
+    let pid = std::process::id();
+
+    let mut rules = RuleSet::new();
+    rules
+        .add(rule(
+            "spawned",
+            RuleMatch {
+                ancestor_pid: Some(pid),
+                ..Default::default()
+            },
+            RuleActions {
+                visible: Some(true),
+                ..Default::default()
+            },
+        ))
+        .unwrap();
+
+    let mut props = firefox();
+    assert_eq!(rules.actions_for(&props), None);
+
+    props.pid = Some(pid);
+    assert!(rules.actions_for(&props).is_some());
+}
+
+#[test]
+fn test_rules_replace_and_remove() {
+    let mut rules = RuleSet::new();
+    rules
+        .add(rule("a", RuleMatch::default(), RuleActions::default()))
+        .unwrap();
+    rules
+        .add(rule("b", RuleMatch::default(), RuleActions::default()))
+        .unwrap();
+
+    let replacement = rule(
+        "a",
+        RuleMatch {
+            role: Some("browser".to_owned()),
+            ..Default::default()
+        },
+        RuleActions::default(),
+    );
+    rules.add(replacement.clone()).unwrap();
+
+    let listed = rules.list();
+    assert_eq!(listed.len(), 2);
+    assert_eq!(listed[0], replacement);
+
+    assert!(rules.remove("a"));
+    assert!(!rules.remove("a"));
+    assert_eq!(rules.list().len(), 1);
+}
+
+#[test]
+fn test_rules_invalid_title_pattern() {
+    let mut rules = RuleSet::new();
+    let result = rules.add(rule(
+        "broken",
+        RuleMatch {
+            title: Some("(".to_owned()),
+            ..Default::default()
+        },
+        RuleActions::default(),
+    ));
+    assert!(result.is_err());
+    assert!(rules.list().is_empty());
+}
+
+#[test]
+fn test_rules_invalid_actions() {
+    let invalid = [
+        RuleActions {
+            workspace: Some(String::new()),
+            ..Default::default()
+        },
+        RuleActions {
+            x: Some(Length::Pixels(40_000)),
+            ..Default::default()
+        },
+        RuleActions {
+            width: Some(Length::Pixels(0)),
+            ..Default::default()
+        },
+        RuleActions {
+            height: Some(Length::Percent(0.0)),
+            ..Default::default()
+        },
+    ];
+
+    let mut rules = RuleSet::new();
+    for actions in invalid.iter().cloned() {
+        assert!(rules
+            .add(rule("broken", RuleMatch::default(), actions))
+            .is_err());
+    }
+    assert!(rules.list().is_empty());
+
+    let actions = RuleActions {
+        x: Some(Length::Pixels(-100)),
+        width: Some(Length::Percent(50.0)),
+        ..Default::default()
+    };
+    assert!(rules
+        .add(rule("fine", RuleMatch::default(), actions))
+        .is_ok());
+}
@@ -0,0 +1,121 @@
+use std::env;
+use std::fs;
+
+use x11wmgr::messages::{Request, Scene, SceneWindow, WindowProperties};
+use x11wmgr::scenes::{match_windows, SceneStore};
+
+fn entry(class: &str, title: &str, zindex: u32) -> SceneWindow {
+    SceneWindow {
+        class: Some(class.to_owned()),
+        instance: Some(class.to_lowercase()),
+        title: Some(title.to_owned()),
+        visible: true,
+        zindex,
+        x: 0,
+        y: 0,
+        width: 640,
+        height: 480,
+    }
+}
+
+fn props(class: &str, title: &str) -> WindowProperties {
+    WindowProperties {
+        class: Some(class.to_owned()),
+        instance: Some(class.to_lowercase()),
+        title: Some(title.to_owned()),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_request_save_scene_serialization() {
+    let request = Request::SaveScene {
+        name: "morning".to_owned(),
+        windows: Some(vec![1, 2]),
+    };
+
+    let serialized = serde_json::to_string(&request).unwrap();
+    let expected = r#"{"SaveScene":{"name":"morning","windows":[1,2]}}"#;
+    assert_eq!(serialized, expected);
+
+    let deserialized: Request =
+        serde_json::from_str(r#"{"SaveScene":{"name":"morning"}}"#).unwrap();
+    assert_eq!(
+        deserialized,
+        Request::SaveScene {
+            name: "morning".to_owned(),
+            windows: None,
+        }
+    );
+}
+
+#[test]
+fn test_match_windows_prefers_title() {
+    let scene = Scene {
+        name: "signage".to_owned(),
+        windows: vec![
+            entry("Chromium", "Menu", 1),
+            entry("Chromium", "Specials", 2),
+        ],
+    };
+    let candidates = vec![
+        (10, props("Chromium", "Specials")),
+        (11, props("Chromium", "Menu")),
+        (12, props("XTerm", "Menu")),
+    ];
+
+    let matched = match_windows(&scene, &candidates);
+    let matched = matched
+        .into_iter()
+        .map(|(id, entry)| (id, entry.zindex))
+        .collect::<Vec<_>>();
+    assert_eq!(matched, vec![(11, 1), (10, 2)]);
+}
+
+#[test]
+fn test_match_windows_falls_back_to_class() {
+    let scene = Scene {
+        name: "signage".to_owned(),
+        windows: vec![
+            entry("Chromium", "Menu", 1),
+            entry("Chromium", "Specials", 2),
+            entry("Chromium", "Weather", 3),
+        ],
+    };
+    let candidates = vec![
+        (10, props("Chromium", "Loading...")),
+        (11, props("Chromium", "Specials")),
+    ];
+
+    let matched = match_windows(&scene, &candidates);
+    let matched = matched
+        .into_iter()
+        .map(|(id, entry)| (id, entry.zindex))
+        .collect::<Vec<_>>();
+    assert_eq!(matched, vec![(10, 1), (11, 2)]);
+}
+
+#[test]
+fn test_scene_store_persistence() {
+    let dir = env::temp_dir().join(format!("x11wmgr-scenes-{}", std::process::id()));
+    let path = dir.join("scenes.json");
+    let _ = fs::remove_dir_all(&dir);
+
+    let mut store = SceneStore::open(&path).unwrap();
+    assert!(store.list().is_empty());
+
+    let scene = Scene {
+        name: "evening".to_owned(),
+        windows: vec![entry("Chromium", "Menu", 1)],
+    };
+    store.save(scene.clone()).unwrap();
+
+    let mut store = SceneStore::open(&path).unwrap();
+    assert_eq!(store.list(), vec![scene]);
+
+    assert!(store.delete("evening").unwrap());
+    assert!(!store.delete("evening").unwrap());
+    assert!(SceneStore::open(&path).unwrap().list().is_empty());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,18 +1,25 @@
-use serde_json;
-use x11wmgr::messages::{Request, Response, WinMove, WinResize, WinVisbilty, WinZIndex, WindowInfo};
+use serde_json::json;
+use x11wmgr::messages::{
+    Change, CommitReport, ConfigureOp, ErrorType, Event, Input, Length, OutputInfo, PendingChanges,
+    Request, RequestEnvelope, Response, ResponseBody, ResponseEnvelope, StackLayer, StackPosition,
+    WinChanges, WinClosed, WinConfigure, WinFailure, WinGeometry, WinLayer, WinLayout, WinMove,
+    WinResize, WinVisbilty, WinWorkspace, WinZIndex, WindowInfo, WindowProperties, WorkspaceInfo,
+};
 
 #[test]
 fn test_request_move_windows_serialization() {
     let request = Request::MoveWindows(vec![
         WinMove {
             id: 1,
-            x: 100,
-            y: 200,
+            output: None,
+            x: Length::Pixels(100),
+            y: Length::Pixels(200),
         },
         WinMove {
             id: 2,
-            x: -50,
-            y: -75,
+            output: None,
+            x: Length::Pixels(-50),
+            y: Length::Pixels(-75),
         },
     ]);
 
@@ -29,13 +36,17 @@ fn test_request_resize_windows_serialization() {
     let request = Request::ResizeWindows(vec![
         WinResize {
             id: 1,
-            width: 800,
-            height: 600,
+            output: None,
+            width: Length::Pixels(800),
+            height: Length::Pixels(600),
+            ignore_hints: false,
         },
         WinResize {
             id: 2,
-            width: 1024,
-            height: 768,
+            output: None,
+            width: Length::Pixels(1024),
+            height: Length::Pixels(768),
+            ignore_hints: false,
         },
     ]);
 
@@ -97,7 +108,7 @@ fn test_request_list_new_windows_serialization() {
 
 #[test]
 fn test_request_commit_serialization() {
-    let request = Request::Commit;
+    let request = Request::Commit { dry_run: false };
 
     let serialized = serde_json::to_string(&request).unwrap();
     let expected = r#""Commit""#;
@@ -107,6 +118,21 @@ fn test_request_commit_serialization() {
     assert_eq!(deserialized, request);
 }
 
+#[test]
+fn test_request_commit_dry_run_serialization() {
+    let request = Request::Commit { dry_run: true };
+
+    let serialized = serde_json::to_string(&request).unwrap();
+    let expected = r#"{"Commit":{"dry_run":true}}"#;
+    assert_eq!(serialized, expected);
+
+    let deserialized: Request = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, request);
+
+    let deserialized: Request = serde_json::from_str(r#"{"Commit":{}}"#).unwrap();
+    assert_eq!(deserialized, Request::Commit { dry_run: false });
+}
+
 #[test]
 fn test_response_new_windows_serialization() {
     let response = Response::NewWindows(vec![
@@ -116,6 +142,7 @@ fn test_response_new_windows_serialization() {
             y: 200,
             width: 800,
             height: 600,
+            properties: WindowProperties::default(),
         },
         WindowInfo {
             id: 2,
@@ -123,6 +150,7 @@ fn test_response_new_windows_serialization() {
             y: -75,
             width: 1024,
             height: 768,
+            properties: WindowProperties::default(),
         },
     ]);
 
@@ -167,6 +195,7 @@ fn test_response_visible_windows_serialization() {
             y: 200,
             width: 800,
             height: 600,
+            properties: WindowProperties::default(),
         },
         WindowInfo {
             id: 2,
@@ -174,6 +203,7 @@ fn test_response_visible_windows_serialization() {
             y: -75,
             width: 1024,
             height: 768,
+            properties: WindowProperties::default(),
         },
     ]);
 
@@ -194,6 +224,7 @@ fn test_response_hidden_windows_serialization() {
             y: 200,
             width: 800,
             height: 600,
+            properties: WindowProperties::default(),
         },
         WindowInfo {
             id: 2,
@@ -201,6 +232,7 @@ fn test_response_hidden_windows_serialization() {
             y: -75,
             width: 1024,
             height: 768,
+            properties: WindowProperties::default(),
         },
     ]);
 
@@ -214,7 +246,7 @@ fn test_response_hidden_windows_serialization() {
 
 #[test]
 fn test_response_commit_complete_serialization() {
-    let response = Response::CommitComplete;
+    let response = Response::CommitComplete(CommitReport::default());
 
     let serialized = serde_json::to_string(&response).unwrap();
     let expected = r#""CommitComplete""#;
@@ -222,6 +254,54 @@ fn test_response_commit_complete_serialization() {
 
     let deserialized: Response = serde_json::from_str(&serialized).unwrap();
     assert_eq!(deserialized, response);
+
+    let response = Response::CommitComplete(CommitReport {
+        failures: vec![WinFailure {
+            id: 7,
+            error: "X11 error 3 (Window) in ConfigureWindow".to_owned(),
+        }],
+        applied: vec![WinGeometry {
+            id: 8,
+            x: 0,
+            y: 0,
+            width: 796,
+            height: 592,
+        }],
+    });
+
+    let serialized = serde_json::to_string(&response).unwrap();
+    let expected = r#"{"CommitComplete":{"failures":[{"id":7,"error":"X11 error 3 (Window) in ConfigureWindow"}],"applied":[{"id":8,"x":0,"y":0,"width":796,"height":592}]}}"#;
+    assert_eq!(serialized, expected);
+
+    let deserialized: Response = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, response);
+}
+
+#[test]
+fn test_response_layout_applied_serialization() {
+    let response = Response::LayoutApplied(CommitReport::default());
+
+    let serialized = serde_json::to_string(&response).unwrap();
+    let expected = r#""LayoutApplied""#;
+    assert_eq!(serialized, expected);
+
+    let deserialized: Response = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, response);
+
+    let response = Response::LayoutApplied(CommitReport {
+        failures: vec![WinFailure {
+            id: 7,
+            error: "X11 error 3 (Window) in ConfigureWindow".to_owned(),
+        }],
+        applied: vec![],
+    });
+
+    let serialized = serde_json::to_string(&response).unwrap();
+    let expected = r#"{"LayoutApplied":{"failures":[{"id":7,"error":"X11 error 3 (Window) in ConfigureWindow"}]}}"#;
+    assert_eq!(serialized, expected);
+
+    let deserialized: Response = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, response);
 }
 
 #[test]
@@ -259,3 +339,421 @@ fn test_response_window_focused_serialization() {
     let deserialized: Response = serde_json::from_str(&serialized).unwrap();
     assert_eq!(deserialized, response);
 }
+
+#[test]
+fn test_request_subscribe_serialization() {
+    let request = Request::Subscribe;
+
+    let serialized = serde_json::to_string(&request).unwrap();
+    let expected = r#""Subscribe""#;
+    assert_eq!(serialized, expected);
+
+    let deserialized: Request = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, request);
+}
+
+#[test]
+fn test_event_configure_requested_serialization() {
+    let event = Event::ConfigureRequested(WinConfigure {
+        id: 1,
+        x: Some(10),
+        y: None,
+        width: Some(640),
+        height: None,
+    });
+
+    let serialized = serde_json::to_string(&event).unwrap();
+    let expected = r#"{"ConfigureRequested":{"id":1,"x":10,"width":640}}"#;
+    assert_eq!(serialized, expected);
+
+    let deserialized: Event = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, event);
+}
+
+#[test]
+fn test_event_window_unmapped_serialization() {
+    let event = Event::WindowUnmapped(7);
+
+    let serialized = serde_json::to_string(&event).unwrap();
+    let expected = r#"{"WindowUnmapped":7}"#;
+    assert_eq!(serialized, expected);
+
+    let deserialized: Event = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, event);
+}
+
+#[test]
+fn test_event_window_iconified_serialization() {
+    let event = Event::WindowIconified(7);
+
+    let serialized = serde_json::to_string(&event).unwrap();
+    let expected = r#"{"WindowIconified":7}"#;
+    assert_eq!(serialized, expected);
+
+    let deserialized: Event = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, event);
+}
+
+#[test]
+fn test_input_envelope_deserialization() {
+    let input: Input = serde_json::from_str(r#"{"id":"a1","request":"Commit"}"#).unwrap();
+    let envelope: RequestEnvelope = input.into();
+    assert_eq!(
+        envelope,
+        RequestEnvelope {
+            id: Some(json!("a1")),
+            request: Request::Commit { dry_run: false },
+        }
+    );
+
+    let input: Input = serde_json::from_str(r#"{"FocusWindow":42}"#).unwrap();
+    let envelope: RequestEnvelope = input.into();
+    assert_eq!(
+        envelope,
+        RequestEnvelope {
+            id: None,
+            request: Request::FocusWindow(42),
+        }
+    );
+}
+
+#[test]
+fn test_response_envelope_serialization() {
+    let envelope = ResponseEnvelope {
+        id: Some(json!(7)),
+        body: ResponseBody::Result(Response::CommitComplete(CommitReport::default())),
+    };
+
+    let serialized = serde_json::to_string(&envelope).unwrap();
+    let expected = r#"{"id":7,"Result":"CommitComplete"}"#;
+    assert_eq!(serialized, expected);
+
+    let deserialized: ResponseEnvelope = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, envelope);
+}
+
+#[test]
+fn test_response_envelope_without_id_serialization() {
+    let envelope = ResponseEnvelope {
+        id: None,
+        body: ResponseBody::Error(ErrorType::InvalidInput("bogus".to_owned())),
+    };
+
+    let serialized = serde_json::to_string(&envelope).unwrap();
+    let expected = r#"{"Error":{"InvalidInput":"bogus"}}"#;
+    assert_eq!(serialized, expected);
+
+    let deserialized: ResponseEnvelope = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, envelope);
+}
+
+#[test]
+fn test_window_info_properties_serialization() {
+    let info = WindowInfo {
+        id: 1,
+        x: 0,
+        y: 0,
+        width: 640,
+        height: 480,
+        properties: WindowProperties {
+            title: Some("Terminal".to_owned()),
+            class: Some("XTerm".to_owned()),
+            instance: Some("xterm".to_owned()),
+            pid: Some(4242),
+            role: None,
+            client_machine: Some("kiosk".to_owned()),
+            window_type: vec!["NORMAL".to_owned()],
+            window_state: vec![],
+        },
+    };
+
+    let serialized = serde_json::to_string(&info).unwrap();
+    let expected = r#"{"id":1,"x":0,"y":0,"width":640,"height":480,"title":"Terminal","class":"XTerm","instance":"xterm","pid":4242,"client_machine":"kiosk","window_type":["NORMAL"]}"#;
+    assert_eq!(serialized, expected);
+
+    let deserialized: WindowInfo = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, info);
+}
+
+#[test]
+fn test_request_close_windows_serialization() {
+    let request = Request::CloseWindows(vec![1, 2]);
+
+    let serialized = serde_json::to_string(&request).unwrap();
+    let expected = r#"{"CloseWindows":[1,2]}"#;
+    assert_eq!(serialized, expected);
+
+    let deserialized: Request = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, request);
+}
+
+#[test]
+fn test_response_windows_closed_serialization() {
+    let response = Response::WindowsClosed(WinClosed {
+        asked: vec![1],
+        killed: vec![2],
+        ..Default::default()
+    });
+
+    let serialized = serde_json::to_string(&response).unwrap();
+    let expected = r#"{"WindowsClosed":{"asked":[1],"killed":[2]}}"#;
+    assert_eq!(serialized, expected);
+
+    let deserialized: Response = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, response);
+
+    let response = Response::WindowsClosed(WinClosed {
+        asked: vec![1],
+        killed: vec![],
+        failures: vec![WinFailure {
+            id: 3,
+            error: "BadWindow".to_string(),
+        }],
+    });
+
+    let serialized = serde_json::to_string(&response).unwrap();
+    let expected =
+        r#"{"WindowsClosed":{"asked":[1],"killed":[],"failures":[{"id":3,"error":"BadWindow"}]}}"#;
+    assert_eq!(serialized, expected);
+
+    let deserialized: Response = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, response);
+}
+
+#[test]
+fn test_response_outputs_serialization() {
+    let response = Response::Outputs(vec![OutputInfo {
+        name: "HDMI-1".to_owned(),
+        x: 1920,
+        y: 0,
+        width: 1080,
+        height: 1920,
+        primary: false,
+        rotation: 90,
+    }]);
+
+    let serialized = serde_json::to_string(&response).unwrap();
+    let expected = r#"{"Outputs":[{"name":"HDMI-1","x":1920,"y":0,"width":1080,"height":1920,"primary":false,"rotation":90}]}"#;
+    assert_eq!(serialized, expected);
+
+    let deserialized: Response = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, response);
+}
+
+#[test]
+fn test_request_move_windows_relative_deserialization() {
+    let request: Request = serde_json::from_str(
+        r#"{"MoveWindows":[{"id":1,"output":"HDMI-1","x":"50%","y":0},{"id":2,"x":0.25,"y":"10"}]}"#,
+    )
+    .unwrap();
+
+    let expected = Request::MoveWindows(vec![
+        WinMove {
+            id: 1,
+            output: Some("HDMI-1".to_owned()),
+            x: Length::Percent(50.0),
+            y: Length::Pixels(0),
+        },
+        WinMove {
+            id: 2,
+            output: None,
+            x: Length::Percent(25.0),
+            y: Length::Pixels(10),
+        },
+    ]);
+    assert_eq!(request, expected);
+
+    let serialized = serde_json::to_string(&expected).unwrap();
+    let expected =
+        r#"{"MoveWindows":[{"id":1,"output":"HDMI-1","x":"50%","y":0},{"id":2,"x":"25%","y":10}]}"#;
+    assert_eq!(serialized, expected);
+}
+
+#[test]
+fn test_request_resize_windows_invalid_length() {
+    let result = serde_json::from_str::<Request>(
+        r#"{"ResizeWindows":[{"id":1,"width":"wide","height":10}]}"#,
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_length_resolve() {
+    assert_eq!(Length::Pixels(-20).resolve(1920), -20);
+    assert_eq!(Length::Percent(50.0).resolve(1920), 960);
+    assert_eq!(Length::Percent(33.3).resolve(1000), 333);
+}
+
+#[test]
+fn test_request_move_to_workspace_serialization() {
+    let request = Request::MoveToWorkspace(vec![WinWorkspace {
+        id: 1,
+        workspace: "web".to_owned(),
+    }]);
+
+    let serialized = serde_json::to_string(&request).unwrap();
+    let expected = r#"{"MoveToWorkspace":[{"id":1,"workspace":"web"}]}"#;
+    assert_eq!(serialized, expected);
+
+    let deserialized: Request = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, request);
+}
+
+#[test]
+fn test_response_workspaces_serialization() {
+    let response = Response::Workspaces(vec![
+        WorkspaceInfo {
+            name: "1".to_owned(),
+            current: true,
+            visible: vec![1, 2],
+            hidden: vec![],
+        },
+        WorkspaceInfo {
+            name: "web".to_owned(),
+            current: false,
+            visible: vec![],
+            hidden: vec![3],
+        },
+    ]);
+
+    let serialized = serde_json::to_string(&response).unwrap();
+    let expected = r#"{"Workspaces":[{"name":"1","current":true,"visible":[1,2],"hidden":[]},{"name":"web","current":false,"visible":[],"hidden":[3]}]}"#;
+    assert_eq!(serialized, expected);
+
+    let deserialized: Response = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, response);
+}
+
+#[test]
+fn test_request_apply_layout_deserialization() {
+    let request: Request = serde_json::from_str(
+        r#"{"ApplyLayout":[{"id":1,"visible":true,"zindex":2,"x":0,"y":0,"width":"50%","height":"100%"},{"id":2,"visible":false}]}"#,
+    )
+    .unwrap();
+
+    let expected = Request::ApplyLayout(vec![
+        WinLayout {
+            id: 1,
+            output: None,
+            visible: Some(true),
+            zindex: Some(2),
+            x: Some(Length::Pixels(0)),
+            y: Some(Length::Pixels(0)),
+            width: Some(Length::Percent(50.0)),
+            height: Some(Length::Percent(100.0)),
+            ignore_hints: false,
+        },
+        WinLayout {
+            id: 2,
+            output: None,
+            visible: Some(false),
+            zindex: None,
+            x: None,
+            y: None,
+            width: None,
+            height: None,
+            ignore_hints: false,
+        },
+    ]);
+    assert_eq!(request, expected);
+
+    let serialized = serde_json::to_string(&expected).unwrap();
+    let reparsed: Request = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(reparsed, expected);
+}
+
+#[test]
+fn test_response_pending_changes_serialization() {
+    let response = Response::PendingChanges(PendingChanges {
+        workspace: Some(Change {
+            from: "1".to_owned(),
+            to: "web".to_owned(),
+        }),
+        windows: vec![WinChanges {
+            id: 1,
+            visible: Some(Change {
+                from: false,
+                to: true,
+            }),
+            zindex: None,
+            workspace: None,
+            layer: None,
+            x: Some(Change { from: 0, to: 100 }),
+            y: None,
+            width: None,
+            height: None,
+        }],
+    });
+
+    let serialized = serde_json::to_string(&response).unwrap();
+    let expected = r#"{"PendingChanges":{"workspace":{"from":"1","to":"web"},"windows":[{"id":1,"visible":{"from":false,"to":true},"x":{"from":0,"to":100}}]}}"#;
+    assert_eq!(serialized, expected);
+
+    let deserialized: Response = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, response);
+}
+
+#[test]
+fn test_response_dry_run_serialization() {
+    let response = Response::DryRun(vec![
+        ConfigureOp {
+            id: 2,
+            x: None,
+            y: None,
+            width: None,
+            height: None,
+            stack_mode: StackPosition::Below,
+        },
+        ConfigureOp {
+            id: 1,
+            x: Some(10),
+            y: None,
+            width: Some(640),
+            height: None,
+            stack_mode: StackPosition::Above,
+        },
+    ]);
+
+    let serialized = serde_json::to_string(&response).unwrap();
+    let expected = r#"{"DryRun":[{"id":2,"stack_mode":"Below"},{"id":1,"x":10,"width":640,"stack_mode":"Above"}]}"#;
+    assert_eq!(serialized, expected);
+
+    let deserialized: Response = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, response);
+}
+
+#[test]
+fn test_request_restack_serialization() {
+    let request = Request::StackAbove { id: 1, sibling: 2 };
+
+    let serialized = serde_json::to_string(&request).unwrap();
+    let expected = r#"{"StackAbove":{"id":1,"sibling":2}}"#;
+    assert_eq!(serialized, expected);
+
+    let deserialized: Request = serde_json::from_str(r#"{"RaiseWindow":1}"#).unwrap();
+    assert_eq!(deserialized, Request::RaiseWindow(1));
+
+    let serialized = serde_json::to_string(&Response::Restacked(true)).unwrap();
+    assert_eq!(serialized, r#"{"Restacked":true}"#);
+}
+
+#[test]
+fn test_request_set_layer_serialization() {
+    let request = Request::SetLayer(vec![
+        WinLayer {
+            id: 1,
+            layer: Some(StackLayer::Overlay),
+        },
+        WinLayer { id: 2, layer: None },
+    ]);
+
+    let serialized = serde_json::to_string(&request).unwrap();
+    let expected = r#"{"SetLayer":[{"id":1,"layer":"Overlay"},{"id":2,"layer":null}]}"#;
+    assert_eq!(serialized, expected);
+
+    let deserialized: Request = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, request);
+
+    assert!(StackLayer::Below < StackLayer::Normal);
+    assert!(StackLayer::Above < StackLayer::Overlay);
+}
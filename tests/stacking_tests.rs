@@ -0,0 +1,75 @@
+use std::time::{Duration, Instant};
+
+use x11wmgr::messages::{StackLayer, WindowProperties};
+use x11wmgr::stacking::{commit_order, implied_layer, stacking_order, StackEntry};
+
+fn entry(id: u32, shown: bool, layer: StackLayer, zindex: u32, updated: u64) -> StackEntry {
+    let start = Instant::now();
+    StackEntry {
+        id,
+        shown,
+        layer,
+        zindex,
+        updated: start + Duration::from_secs(updated),
+        discovered: start,
+    }
+}
+
+#[test]
+fn test_commit_order_by_layer_and_zindex() {
+    let entries = [
+        entry(1, true, StackLayer::Normal, 5, 0),
+        entry(2, true, StackLayer::Above, 0, 0),
+        entry(3, true, StackLayer::Normal, 1, 0),
+        entry(4, true, StackLayer::Below, 9, 0),
+    ];
+    let (shown, hidden) = commit_order(&entries);
+    assert_eq!(shown, vec![4, 3, 1, 2]);
+    assert!(hidden.is_empty());
+}
+
+#[test]
+fn test_commit_order_ties_go_to_the_last_updated() {
+    let entries = [
+        entry(1, true, StackLayer::Normal, 1, 2),
+        entry(2, true, StackLayer::Normal, 1, 1),
+        entry(3, true, StackLayer::Normal, 1, 3),
+    ];
+    let (shown, _) = commit_order(&entries);
+    assert_eq!(shown, vec![2, 1, 3]);
+}
+
+#[test]
+fn test_hidden_windows_end_up_below_in_order() {
+    let entries = [
+        entry(1, false, StackLayer::Normal, 1, 0),
+        entry(2, true, StackLayer::Normal, 0, 0),
+        entry(3, false, StackLayer::Above, 3, 0),
+        entry(4, false, StackLayer::Normal, 2, 0),
+    ];
+
+    // hidden windows are pushed to the bottom from the top one down, regardless of layer
+    let (shown, hidden) = commit_order(&entries);
+    assert_eq!(shown, vec![2]);
+    assert_eq!(hidden, vec![3, 4, 1]);
+
+    assert_eq!(stacking_order(&shown, &hidden), vec![1, 4, 3, 2]);
+}
+
+#[test]
+fn test_implied_layer() {
+    let mut properties = WindowProperties::default();
+    assert_eq!(implied_layer(None, &properties), StackLayer::Normal);
+
+    properties.window_type = vec!["DOCK".to_owned()];
+    assert_eq!(implied_layer(None, &properties), StackLayer::Above);
+
+    properties.window_state = vec!["BELOW".to_owned()];
+    assert_eq!(implied_layer(None, &properties), StackLayer::Below);
+
+    // an explicitly set layer always wins
+    assert_eq!(
+        implied_layer(Some(StackLayer::Overlay), &properties),
+        StackLayer::Overlay
+    );
+}